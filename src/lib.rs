@@ -0,0 +1,2130 @@
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{
+    format::{Item, StrftimeItems},
+    Datelike, Duration as DateDuration, Local, Locale, Months, NaiveDate, Weekday,
+};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Write},
+    num::ParseIntError,
+    path::{Path, PathBuf},
+    process,
+};
+
+const PROTECTED_MARKER: &str = "🔒";
+const PINNED_MARKER: &str = "📌";
+const STARRED_MARKER: &str = "⭐";
+const URL_MARKER: &str = "🔗";
+const CARRIED_MARKER: &str = "↪";
+const DUE_MARKER: &str = "📅";
+const TARGET_MARKER: &str = "🎯";
+const REPEAT_MARKER: &str = "🔁";
+const CREATED_MARKER: &str = "➕";
+const SELECTED_MARKER: &str = "🛫";
+const DONE_MARKER: &str = "✅";
+const UPDATED_MARKER: &str = "✏";
+const ADDED_BY_MARKER: &str = "👤";
+const CHECKED_BY_MARKER: &str = "🏁";
+const HIGH_PRIORITY_MARKER: &str = "⏫";
+const MEDIUM_PRIORITY_MARKER: &str = "🔼";
+const LOW_PRIORITY_MARKER: &str = "🔽";
+pub const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// How dates are shown to people. Task files always use `DATE_FORMAT`.
+#[derive(Clone, Debug)]
+pub struct DateStyle {
+    format: String,
+    locale: Locale,
+    week_start: Weekday,
+}
+
+impl Default for DateStyle {
+    fn default() -> Self {
+        DateStyle {
+            format: String::from(DATE_FORMAT),
+            locale: Locale::POSIX,
+            week_start: Weekday::Mon,
+        }
+    }
+}
+
+impl DateStyle {
+    /// A style from a strftime `format`, a locale name such as `fi_FI`
+    /// used for month and day names, and the first day of the week.
+    pub fn new(format: &str, locale: &str, week_start: &str) -> Result<DateStyle> {
+        if StrftimeItems::new(format).any(|item| item == Item::Error) {
+            bail!("invalid date format `{}`", format);
+        }
+        let locale = Locale::try_from(locale)
+            .map_err(|_| anyhow!("unknown locale `{}`, expected e.g. `en_US`", locale))?;
+        let week_start: Weekday = week_start
+            .parse()
+            .map_err(|_| anyhow!("invalid first day of week `{}`", week_start))?;
+        Ok(DateStyle {
+            format: format.to_string(),
+            locale,
+            week_start,
+        })
+    }
+
+    pub fn format(&self, date: NaiveDate) -> String {
+        date.format_localized(&self.format, self.locale).to_string()
+    }
+
+    /// `date` in another strftime `format`, with month and day names in
+    /// this style's locale.
+    pub fn format_as(&self, date: NaiveDate, format: &str) -> String {
+        date.format_localized(format, self.locale).to_string()
+    }
+
+    /// First day of the week that `date` falls in.
+    pub fn week_of(&self, date: NaiveDate) -> NaiveDate {
+        date.week(self.week_start).first_day()
+    }
+}
+
+/// Markers that can follow the task text, with what each one means.
+pub const MARKERS: [(&str, &str); 18] = [
+    ("#", "tag"),
+    (HIGH_PRIORITY_MARKER, "high priority"),
+    (MEDIUM_PRIORITY_MARKER, "medium priority"),
+    (LOW_PRIORITY_MARKER, "low priority"),
+    (TARGET_MARKER, "target date"),
+    (DUE_MARKER, "deadline"),
+    (REPEAT_MARKER, "repeats"),
+    (URL_MARKER, "link"),
+    (CARRIED_MARKER, "carried over"),
+    (STARRED_MARKER, "starred"),
+    (PINNED_MARKER, "pinned"),
+    (PROTECTED_MARKER, "protected"),
+    (CREATED_MARKER, "added on"),
+    (SELECTED_MARKER, "selected on"),
+    (DONE_MARKER, "completed on"),
+    (UPDATED_MARKER, "changed on"),
+    (ADDED_BY_MARKER, "added by"),
+    (CHECKED_BY_MARKER, "checked by"),
+];
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    High,
+    Medium,
+    Low,
+}
+
+impl Priority {
+    pub fn marker(&self) -> &'static str {
+        match self {
+            Priority::High => HIGH_PRIORITY_MARKER,
+            Priority::Medium => MEDIUM_PRIORITY_MARKER,
+            Priority::Low => LOW_PRIORITY_MARKER,
+        }
+    }
+}
+
+impl Display for Priority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Priority::High => write!(f, "high"),
+            Priority::Medium => write!(f, "medium"),
+            Priority::Low => write!(f, "low"),
+        }
+    }
+}
+
+/// How often a task comes back after it is checked.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Repeat {
+    pub every: u32,
+    pub unit: RepeatUnit,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RepeatUnit {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl Repeat {
+    /// The due date of the next occurrence after one due on `from`.
+    pub fn next(&self, from: NaiveDate) -> Option<NaiveDate> {
+        match self.unit {
+            RepeatUnit::Day => from.checked_add_signed(DateDuration::days(self.every.into())),
+            RepeatUnit::Week => from.checked_add_signed(DateDuration::weeks(self.every.into())),
+            RepeatUnit::Month => from.checked_add_months(Months::new(self.every)),
+            RepeatUnit::Year => from.checked_add_months(Months::new(self.every * 12)),
+        }
+    }
+}
+
+impl Display for Repeat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.every, self.unit) {
+            (1, RepeatUnit::Day) => write!(f, "daily"),
+            (1, RepeatUnit::Week) => write!(f, "weekly"),
+            (1, RepeatUnit::Month) => write!(f, "monthly"),
+            (1, RepeatUnit::Year) => write!(f, "yearly"),
+            (every, RepeatUnit::Day) => write!(f, "{}d", every),
+            (every, RepeatUnit::Week) => write!(f, "{}w", every),
+            (every, RepeatUnit::Month) => write!(f, "{}m", every),
+            (every, RepeatUnit::Year) => write!(f, "{}y", every),
+        }
+    }
+}
+
+impl Serialize for Repeat {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Where to move a task within its section.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Placement {
+    Up,
+    Down,
+    Top,
+    Bottom,
+    Before(usize),
+    After(usize),
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskStatus {
+    Selected,
+    Incomplete,
+    Complete,
+}
+
+impl Display for TaskStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TaskStatus::Selected => write!(f, "selected"),
+            TaskStatus::Incomplete => write!(f, "incomplete"),
+            TaskStatus::Complete => write!(f, "complete"),
+        }
+    }
+}
+
+impl TaskStatus {
+    pub fn all() -> Vec<TaskStatus> {
+        vec![
+            TaskStatus::Selected,
+            TaskStatus::Incomplete,
+            TaskStatus::Complete,
+        ]
+    }
+}
+
+impl TryFrom<&String> for TaskStatus {
+    type Error = anyhow::Error;
+    fn try_from(section: &String) -> std::result::Result<Self, Self::Error> {
+        if section == "### SELECTED" {
+            return Ok(TaskStatus::Selected);
+        }
+        if section == "### INCOMPLETE" {
+            return Ok(TaskStatus::Incomplete);
+        }
+        if section == "### COMPLETE" {
+            return Ok(TaskStatus::Complete);
+        }
+        Err(anyhow!("Error: could not find status"))
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Task {
+    pub id: usize,
+    #[serde(rename = "text")]
+    pub task: String,
+    #[serde(rename = "status")]
+    pub task_status: TaskStatus,
+    pub protected: bool,
+    pub pinned: bool,
+    pub starred: bool,
+    pub carried: bool,
+    pub url: Option<String>,
+    /// Day the task should be done by, missing it is not a problem yet.
+    pub target: Option<NaiveDate>,
+    /// Hard deadline of the task.
+    pub due: Option<NaiveDate>,
+    pub tags: Vec<String>,
+    pub priority: Option<Priority>,
+    pub repeat: Option<Repeat>,
+    /// Day the task was added.
+    pub created: Option<NaiveDate>,
+    /// Day the task was last selected.
+    pub selected: Option<NaiveDate>,
+    /// Day the task was checked.
+    pub completed: Option<NaiveDate>,
+    /// Day the task was last changed after it was added.
+    pub updated: Option<NaiveDate>,
+    /// Who added the task.
+    pub added_by: Option<String>,
+    /// Who checked the task.
+    pub checked_by: Option<String>,
+    /// Indented lines written under the task.
+    pub notes: Vec<String>,
+    /// Text with date placeholders that each new occurrence of a
+    /// repeating task is generated from.
+    pub template: Option<String>,
+}
+
+impl Task {
+    pub fn new(id: usize, task: String, task_status: TaskStatus) -> Task {
+        Task {
+            id,
+            task,
+            task_status,
+            protected: false,
+            pinned: false,
+            starred: false,
+            carried: false,
+            url: None,
+            target: None,
+            due: None,
+            tags: vec![],
+            priority: None,
+            repeat: None,
+            created: None,
+            selected: None,
+            completed: None,
+            updated: None,
+            added_by: None,
+            checked_by: None,
+            notes: vec![],
+            template: None,
+        }
+    }
+
+    pub fn priority_rank(&self) -> u8 {
+        match self.priority {
+            Some(Priority::High) => 3,
+            Some(Priority::Medium) => 2,
+            None => 1,
+            Some(Priority::Low) => 0,
+        }
+    }
+
+    pub fn is_overdue(&self) -> bool {
+        self.task_status != TaskStatus::Complete
+            && self.due.is_some_and(|due| due < Local::now().date_naive())
+    }
+
+    pub fn missed_target(&self) -> bool {
+        self.task_status != TaskStatus::Complete
+            && self
+                .target
+                .is_some_and(|target| target < Local::now().date_naive())
+    }
+
+    pub fn text(&self) -> String {
+        self.render_text(false, false, &DateStyle::default())
+    }
+
+    pub fn render_text(&self, hyperlinks: bool, color: bool, dates: &DateStyle) -> String {
+        let mut text = self.task.clone();
+        for tag in self.tags.iter() {
+            text.push_str(&format!(" #{}", tag));
+        }
+        if let Some(priority) = self.priority {
+            text.push(' ');
+            text.push_str(priority.marker());
+        }
+        if let Some(target) = self.target {
+            let target = format!("{} {}", TARGET_MARKER, dates.format(target));
+            if color && self.missed_target() {
+                text.push_str(&format!(" \x1b[33m{}\x1b[0m", target));
+            } else {
+                text.push(' ');
+                text.push_str(&target);
+            }
+        }
+        if let Some(due) = self.due {
+            let due = format!("{} {}", DUE_MARKER, dates.format(due));
+            if color && self.is_overdue() {
+                text.push_str(&format!(" \x1b[31m{}\x1b[0m", due));
+            } else {
+                text.push(' ');
+                text.push_str(&due);
+            }
+        }
+        if let Some(repeat) = self.repeat {
+            text.push_str(&format!(" {} {}", REPEAT_MARKER, repeat));
+        }
+        if let Some(url) = &self.url {
+            text.push_str(&format!(" {} ", URL_MARKER));
+            if hyperlinks {
+                text.push_str(&hyperlink(url, url));
+            } else {
+                text.push_str(url);
+            }
+        }
+        if self.carried {
+            text.push(' ');
+            text.push_str(CARRIED_MARKER);
+        }
+        if self.starred {
+            text.push(' ');
+            text.push_str(STARRED_MARKER);
+        }
+        if self.pinned {
+            text.push(' ');
+            text.push_str(PINNED_MARKER);
+        }
+        if self.protected {
+            text.push(' ');
+            text.push_str(PROTECTED_MARKER);
+        }
+        text
+    }
+
+    /// Like `text`, followed by the days the task was added, selected,
+    /// checked and last changed with who added and checked it, and the
+    /// template as an HTML comment so that it stays hidden in
+    /// rendered markdown. Tasks under a journal heading leave out the day
+    /// they were checked.
+    fn file_text(&self, done_marker: bool) -> String {
+        let mut text = self.text();
+        if let Some(created) = self.created {
+            text.push_str(&format!(
+                " {} {}",
+                CREATED_MARKER,
+                created.format(DATE_FORMAT)
+            ));
+        }
+        if let Some(added_by) = &self.added_by {
+            text.push_str(&format!(" {} {}", ADDED_BY_MARKER, added_by));
+        }
+        if let Some(selected) = self.selected {
+            text.push_str(&format!(
+                " {} {}",
+                SELECTED_MARKER,
+                selected.format(DATE_FORMAT)
+            ));
+        }
+        if let Some(completed) = self.completed.filter(|_| done_marker) {
+            text.push_str(&format!(
+                " {} {}",
+                DONE_MARKER,
+                completed.format(DATE_FORMAT)
+            ));
+        }
+        if let Some(checked_by) = &self.checked_by {
+            text.push_str(&format!(" {} {}", CHECKED_BY_MARKER, checked_by));
+        }
+        if let Some(updated) = self.updated {
+            text.push_str(&format!(
+                " {} {}",
+                UPDATED_MARKER,
+                updated.format(DATE_FORMAT)
+            ));
+        }
+        if let Some(template) = &self.template {
+            text.push_str(&format!(" <!-- {} -->", template));
+        }
+        text
+    }
+
+    pub fn describe(&self, dates: &DateStyle) -> String {
+        let mut description = format!("Task {}, status {}", self.id, self.task_status);
+        if self.carried {
+            description.push_str(", carried over");
+        }
+        if self.starred {
+            description.push_str(", starred");
+        }
+        if self.pinned {
+            description.push_str(", pinned");
+        }
+        if self.protected {
+            description.push_str(", protected");
+        }
+        if let Some(priority) = self.priority {
+            description.push_str(&format!(", {} priority", priority));
+        }
+        if !self.tags.is_empty() {
+            description.push_str(&format!(", tags {}", self.tags.join(", ")));
+        }
+        if let Some(target) = self.target {
+            description.push_str(&format!(", target {}", dates.format(target)));
+            if self.missed_target() {
+                description.push_str(", target missed");
+            }
+        }
+        if let Some(due) = self.due {
+            description.push_str(&format!(", due {}", dates.format(due)));
+            if self.is_overdue() {
+                description.push_str(", overdue");
+            }
+        }
+        if let Some(repeat) = self.repeat {
+            description.push_str(&format!(", repeats {}", repeat));
+        }
+        if let Some(created) = self.created {
+            description.push_str(&format!(", added {}", dates.format(created)));
+        }
+        if let Some(selected) = self.selected {
+            description.push_str(&format!(", selected {}", dates.format(selected)));
+        }
+        if let Some(completed) = self.completed {
+            description.push_str(&format!(", completed {}", dates.format(completed)));
+        }
+        if let Some(updated) = self.updated {
+            description.push_str(&format!(", changed {}", dates.format(updated)));
+        }
+        if let Some(added_by) = &self.added_by {
+            description.push_str(&format!(", added by {}", added_by));
+        }
+        if let Some(checked_by) = &self.checked_by {
+            description.push_str(&format!(", checked by {}", checked_by));
+        }
+        if let Some(url) = &self.url {
+            description.push_str(&format!(", link {}", url));
+        }
+        format!("{}: {}", description, self.task)
+    }
+
+    pub fn to_markdown(&self) -> String {
+        self.markdown(true)
+    }
+
+    fn markdown(&self, done_marker: bool) -> String {
+        let completed = if let TaskStatus::Complete = self.task_status {
+            'x'
+        } else {
+            ' '
+        };
+
+        format!(
+            "- [{}] **{}**: {}",
+            completed,
+            self.id,
+            self.file_text(done_marker)
+        )
+    }
+}
+
+impl TryFrom<(String, TaskStatus)> for Task {
+    type Error = anyhow::Error;
+    fn try_from(
+        (task, task_status): (String, TaskStatus),
+    ) -> std::result::Result<Self, Self::Error> {
+        let completed: bool = if task.starts_with("- [ ] **") || task.starts_with("- [x] **") {
+            task.chars().nth(3).unwrap() == 'x'
+        } else {
+            return Err(anyhow!("Error: Start of String {:?} is not valid", task));
+        };
+        if !completed {
+            if let TaskStatus::Complete = task_status {
+                return Err(anyhow!("Error: non complete task cannot be complete"));
+            }
+        }
+        let id: usize = get_id(&task)?;
+        let task: String = task.chars().skip_while(|e| e != &':').skip(2).collect();
+        let mut task = task.trim_end().to_string();
+        let template = strip_template(&mut task);
+        let (mut protected, mut pinned, mut starred, mut carried) = (false, false, false, false);
+        let mut url = None;
+        let mut target = None;
+        let mut due = None;
+        let mut tags = vec![];
+        let mut priority = None;
+        let mut repeat = None;
+        let mut created = None;
+        let mut selected = None;
+        let mut completed = None;
+        let mut updated = None;
+        let mut added_by = None;
+        let mut checked_by = None;
+        loop {
+            if strip_marker(&mut task, PROTECTED_MARKER) {
+                protected = true;
+            } else if strip_marker(&mut task, PINNED_MARKER) {
+                pinned = true;
+            } else if strip_marker(&mut task, STARRED_MARKER) {
+                starred = true;
+            } else if strip_marker(&mut task, CARRIED_MARKER) {
+                carried = true;
+            } else if let Some(value) = strip_field(&mut task, URL_MARKER) {
+                url = Some(value);
+            } else if let Some(date) = strip_date(&mut task, CREATED_MARKER) {
+                created = Some(date);
+            } else if let Some(date) = strip_date(&mut task, SELECTED_MARKER) {
+                selected = Some(date);
+            } else if let Some(date) = strip_date(&mut task, DONE_MARKER) {
+                completed = Some(date);
+            } else if let Some(date) = strip_date(&mut task, UPDATED_MARKER) {
+                updated = Some(date);
+            } else if let Some(name) = strip_field(&mut task, ADDED_BY_MARKER) {
+                added_by = Some(name);
+            } else if let Some(name) = strip_field(&mut task, CHECKED_BY_MARKER) {
+                checked_by = Some(name);
+            } else if let Some(date) = strip_date(&mut task, DUE_MARKER) {
+                due = Some(date);
+            } else if let Some(date) = strip_date(&mut task, TARGET_MARKER) {
+                target = Some(date);
+            } else if let Some(rule) = strip_repeat(&mut task) {
+                repeat = Some(rule);
+            } else if strip_marker(&mut task, HIGH_PRIORITY_MARKER) {
+                priority = Some(Priority::High);
+            } else if strip_marker(&mut task, MEDIUM_PRIORITY_MARKER) {
+                priority = Some(Priority::Medium);
+            } else if strip_marker(&mut task, LOW_PRIORITY_MARKER) {
+                priority = Some(Priority::Low);
+            } else if let Some(tag) = strip_tag(&mut task) {
+                tags.insert(0, tag);
+            } else {
+                break;
+            }
+        }
+        Ok(Task {
+            id,
+            task,
+            task_status,
+            protected,
+            pinned,
+            starred,
+            carried,
+            url,
+            target,
+            due,
+            tags,
+            priority,
+            repeat,
+            created,
+            selected,
+            completed,
+            updated,
+            added_by,
+            checked_by,
+            notes: vec![],
+            template,
+        })
+    }
+}
+
+impl Display for Task {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}\t{}\t{}", self.task_status, self.id, self.text())
+    }
+}
+
+/// A task file loaded into memory.
+#[derive(Clone, Debug)]
+pub struct TaskList {
+    path: PathBuf,
+    /// The file `path` pointed to when it was read.
+    resolved: Option<PathBuf>,
+    tasks: Vec<Task>,
+    /// The tasks as they were read, to find the ones changed since.
+    loaded: Vec<Task>,
+    segments: Vec<Segment>,
+    next_id: usize,
+    journal: bool,
+    /// Who added and checked tasks are recorded as.
+    author: Option<String>,
+}
+
+/// A piece of a task file: lines outside the task sections that are
+/// written back as they were, or the place where a task section goes.
+#[derive(Clone, Debug)]
+enum Segment {
+    Text(Vec<String>),
+    Section(TaskStatus),
+}
+
+impl TaskList {
+    /// Read and parse the task file at `path`. Ids of tasks moved to its
+    /// archive file are not handed out again.
+    pub fn load(path: &PathBuf) -> Result<TaskList> {
+        let lines: Vec<String> = get_lines(path)
+            .with_context(|| format!("could not read lines from file `{:?}`", path))?;
+        let mut list = TaskList::parse(path, lines);
+        list.resolved = fs::canonicalize(path).ok();
+        let archive = archive_path(path);
+        if archive.is_file() {
+            let lines: Vec<String> = get_lines(&archive)
+                .with_context(|| format!("could not read lines from file `{:?}`", archive))?;
+            list.next_id = list.next_id.max(get_next_id(&lines));
+        }
+        Ok(list)
+    }
+
+    /// Read only the tasks in `sections` of the task file at `path`, for
+    /// listings that never write the file back.
+    pub fn load_tasks(path: &PathBuf, sections: &[TaskStatus]) -> Result<Vec<Task>> {
+        let lines: Vec<String> = get_lines(path)
+            .with_context(|| format!("could not read lines from file `{:?}`", path))?;
+        Ok(get_tasks_in_sections(lines, sections.to_vec()))
+    }
+
+    /// Parse task file lines that belong to `path`.
+    pub fn parse(path: &Path, lines: Vec<String>) -> TaskList {
+        let next_id = get_next_id(&lines);
+        let journal = lines
+            .iter()
+            .any(|line| parse_journal_heading(line).is_some());
+        let (tasks, segments) = parse_document(lines, &TaskStatus::all());
+        TaskList {
+            path: path.to_path_buf(),
+            resolved: None,
+            loaded: tasks.clone(),
+            tasks,
+            segments,
+            next_id,
+            journal,
+            author: None,
+        }
+    }
+
+    /// Whether complete tasks are grouped under headings for the day they
+    /// were checked. Files that already have such headings stay in journal
+    /// mode.
+    pub fn is_journal(&self) -> bool {
+        self.journal
+    }
+
+    pub fn set_journal(&mut self, journal: bool) {
+        self.journal = journal;
+    }
+
+    /// Record `author` as who added or checked tasks from now on.
+    pub fn set_author(&mut self, author: Option<String>) {
+        self.author = author;
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Task> {
+        self.tasks.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Task> {
+        self.tasks.iter_mut()
+    }
+
+    /// All tasks in file order, for edits that need to insert or reorder.
+    pub fn tasks_mut(&mut self) -> &mut Vec<Task> {
+        &mut self.tasks
+    }
+
+    pub fn into_tasks(self) -> Vec<Task> {
+        self.tasks
+    }
+
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    pub fn next_id(&self) -> usize {
+        self.next_id
+    }
+
+    pub fn get(&self, id: usize) -> Result<&Task> {
+        self.tasks
+            .iter()
+            .find(|t| t.id == id)
+            .with_context(|| format!("could not find task with id `{:?}`", id))
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Result<&mut Task> {
+        self.tasks
+            .iter_mut()
+            .find(|t| t.id == id)
+            .with_context(|| format!("could not find task with id `{:?}`", id))
+    }
+
+    /// Add a new task to `section` and return it so its metadata can be
+    /// filled in. New selected tasks join the end of the selected queue,
+    /// others go to the top of their section.
+    pub fn add(&mut self, task: String, section: TaskStatus) -> &mut Task {
+        let id = self.next_id;
+        self.next_id += 1;
+        let mut task = Task::new(id, task, section);
+        task.created = Some(Local::now().date_naive());
+        task.added_by = self.author.clone();
+        match section {
+            TaskStatus::Selected => task.selected = task.created,
+            TaskStatus::Complete => {
+                task.completed = task.created;
+                task.checked_by = self.author.clone();
+            }
+            TaskStatus::Incomplete => {}
+        }
+        if section == TaskStatus::Selected {
+            self.tasks.push(task);
+            return self.tasks.last_mut().unwrap();
+        }
+        self.tasks.insert(0, task);
+        &mut self.tasks[0]
+    }
+
+    pub fn remove(&mut self, id: usize) -> Result<Task> {
+        let index = self
+            .tasks
+            .iter()
+            .position(|t| t.id == id)
+            .with_context(|| format!("could not find task with id `{:?}`", id))?;
+        Ok(self.tasks.remove(index))
+    }
+
+    /// Move a task to `section`. Tasks that become selected join the end
+    /// of the selected queue and record the day they were selected.
+    pub fn move_task(&mut self, id: usize, section: TaskStatus) -> Result<()> {
+        if section == TaskStatus::Selected && self.get(id)?.task_status != section {
+            let mut task = self.remove(id)?;
+            task.selected = Some(Local::now().date_naive());
+            self.tasks.push(task);
+        }
+        let author = self.author.clone();
+        let task = self.get_mut(id)?;
+        if section == TaskStatus::Incomplete {
+            task.selected = None;
+        }
+        task.task_status = section;
+        task.completed = match section {
+            TaskStatus::Complete => task.completed.or(Some(Local::now().date_naive())),
+            _ => None,
+        };
+        task.checked_by = match section {
+            TaskStatus::Complete => task.checked_by.take().or(author),
+            _ => None,
+        };
+        Ok(())
+    }
+
+    /// Reorder a task within its section. Outside the selected queue tasks
+    /// are kept sorted by priority, so a task cannot pass one with a
+    /// different priority there.
+    pub fn reorder(&mut self, id: usize, placement: Placement) -> Result<()> {
+        let section = self.get(id)?.task_status;
+        let slots: Vec<usize> = (0..self.tasks.len())
+            .filter(|i| self.tasks[*i].task_status == section)
+            .collect();
+        let mut order = slots.clone();
+        if section != TaskStatus::Selected {
+            order.sort_by_key(|i| std::cmp::Reverse(self.tasks[*i].priority_rank()));
+        }
+        let index = |id: usize| -> Result<usize> {
+            order
+                .iter()
+                .position(|i| self.tasks[*i].id == id)
+                .with_context(|| {
+                    format!("task with id `{:?}` is not in section `{:?}`", id, section)
+                })
+        };
+        let from = index(id)?;
+        let to = match placement {
+            Placement::Up => from.saturating_sub(1),
+            Placement::Down => (from + 1).min(order.len() - 1),
+            Placement::Top => 0,
+            Placement::Bottom => order.len() - 1,
+            Placement::Before(other) | Placement::After(other) => {
+                let mut to = index(other)?;
+                if matches!(placement, Placement::After(_)) {
+                    to += 1;
+                }
+                if to > from {
+                    to -= 1;
+                }
+                to
+            }
+        };
+        let moved = order.remove(from);
+        order.insert(to, moved);
+        if section != TaskStatus::Selected {
+            let ranks: Vec<u8> = order
+                .iter()
+                .map(|i| self.tasks[*i].priority_rank())
+                .collect();
+            if ranks.windows(2).any(|pair| pair[0] < pair[1]) {
+                bail!(
+                    "tasks in section `{:?}` stay sorted by priority, change the priority of task with id `{:?}` first",
+                    section,
+                    id
+                );
+            }
+        }
+        let reordered: Vec<Task> = order.iter().map(|i| self.tasks[*i].clone()).collect();
+        for (slot, task) in slots.into_iter().zip(reordered) {
+            self.tasks[slot] = task;
+        }
+        Ok(())
+    }
+
+    /// Move a selected task to the front of the selected queue.
+    pub fn bump(&mut self, id: usize) -> Result<()> {
+        let status = self.get(id)?.task_status;
+        if status != TaskStatus::Selected {
+            bail!("cannot bump task from section `{:?}`", status);
+        }
+        let task = self.remove(id)?;
+        self.tasks.insert(0, task);
+        Ok(())
+    }
+
+    /// Merge the tasks in `duplicates` into `target`, keeping the
+    /// strongest flags and priority, every tag and note, and the earliest
+    /// dates. Protected duplicates are only merged with `force`.
+    pub fn merge(&mut self, target: usize, duplicates: &[usize], force: bool) -> Result<()> {
+        self.get(target)?;
+        for id in duplicates {
+            if self.get(*id)?.protected && !force {
+                bail!(
+                    "task with id `{:?}` is protected, use `--force` to merge it",
+                    id
+                );
+            }
+        }
+        let mut merged: Vec<Task> = vec![];
+        let mut i = 0;
+        while i < self.tasks.len() {
+            if self.tasks[i].id != target && duplicates.contains(&self.tasks[i].id) {
+                merged.push(self.tasks.remove(i));
+            } else {
+                i += 1;
+            }
+        }
+        let task = self.get_mut(target)?;
+        for duplicate in merged {
+            task.starred |= duplicate.starred;
+            task.pinned |= duplicate.pinned;
+            task.protected |= duplicate.protected;
+            if duplicate.priority_rank() > task.priority_rank() {
+                task.priority = duplicate.priority;
+            }
+            if task.url.is_none() {
+                task.url = duplicate.url;
+            }
+            for tag in duplicate.tags {
+                if !task.tags.contains(&tag) {
+                    task.tags.push(tag);
+                }
+            }
+            task.notes.extend(duplicate.notes);
+            task.target = match (task.target, duplicate.target) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+            task.due = match (task.due, duplicate.due) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+            task.created = match (task.created, duplicate.created) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+        }
+        Ok(())
+    }
+
+    /// The selected task at the front of the queue.
+    pub fn next_selected(&self) -> Option<&Task> {
+        self.tasks
+            .iter()
+            .find(|t| t.task_status == TaskStatus::Selected)
+    }
+
+    /// Mark a task complete. If it repeats, a new incomplete copy due on
+    /// its next date is added and its id returned.
+    pub fn check(&mut self, id: usize) -> Result<Option<usize>> {
+        let author = self.author.clone();
+        let task = self.get_mut(id)?;
+        if task.task_status == TaskStatus::Complete {
+            bail!("cannot move task from section `{:?}`", task.task_status);
+        }
+        task.task_status = TaskStatus::Complete;
+        task.completed = Some(Local::now().date_naive());
+        task.checked_by = author.clone();
+        let repeat = match task.repeat {
+            Some(repeat) => repeat,
+            None => return Ok(None),
+        };
+        let mut next = task.clone();
+        next.task_status = TaskStatus::Incomplete;
+        next.carried = false;
+        next.selected = None;
+        next.completed = None;
+        next.updated = None;
+        next.added_by = author;
+        next.checked_by = None;
+        next.created = Some(Local::now().date_naive());
+        next.due = repeat.next(task.due.unwrap_or_else(|| Local::now().date_naive()));
+        next.target = task.target.and_then(|target| repeat.next(target));
+        let date = next.due.unwrap_or_else(|| Local::now().date_naive());
+        if let Some(text) = next
+            .template
+            .as_deref()
+            .and_then(|t| expand_template(t, date))
+        {
+            next.task = text;
+        }
+        next.id = self.next_id;
+        self.next_id += 1;
+        self.tasks.insert(0, next);
+        Ok(Some(self.next_id - 1))
+    }
+
+    pub fn to_lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = vec![];
+        for segment in self.segments.iter() {
+            match segment {
+                Segment::Text(text) => lines.extend(text.iter().cloned()),
+                Segment::Section(s) => lines = add_section(lines, &self.tasks, *s, self.journal),
+            }
+        }
+        lines
+    }
+
+    /// Write the tasks back to the file they were loaded from, marking
+    /// the ones changed since they were read. Fails if `path` has been
+    /// pointed at another file since it was read.
+    pub fn save(&mut self) -> Result<()> {
+        if let Some(resolved) = &self.resolved {
+            let current = fs::canonicalize(&self.path).ok();
+            if current.as_ref() != Some(resolved) {
+                bail!(
+                    "task file `{:?}` no longer points to `{:?}`, the file that was read",
+                    self.path,
+                    resolved
+                );
+            }
+        }
+        self.stamp_changed(Local::now().date_naive());
+        write_lines(&self.path, &self.to_lines())?;
+        self.loaded = self.tasks.clone();
+        Ok(())
+    }
+
+    /// Set the day changed on every task that differs from how it was
+    /// read. New tasks already carry the day they were added.
+    fn stamp_changed(&mut self, today: NaiveDate) {
+        let loaded: HashMap<usize, &Task> = self.loaded.iter().map(|t| (t.id, t)).collect();
+        for task in self.tasks.iter_mut() {
+            let changed = loaded.get(&task.id).is_some_and(|old| {
+                old.task_status != task.task_status
+                    || old.notes != task.notes
+                    || old.to_markdown() != task.to_markdown()
+            });
+            if changed {
+                task.updated = Some(today);
+            }
+        }
+    }
+}
+
+/// Replace the file at `path` with `lines`. The lines are written to a
+/// temporary file next to it first and then renamed over the original, so
+/// an interrupted write never leaves a partial file behind.
+pub fn write_lines(path: &Path, lines: &[String]) -> Result<()> {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let name = path
+        .file_name()
+        .with_context(|| format!("could not write to file `{:?}`", path))?;
+    let temp = path.with_file_name(format!(".{}.{}.tmp", name.to_string_lossy(), process::id()));
+    let result = (|| -> Result<()> {
+        let mut file = BufWriter::new(File::create(&temp)?);
+        for line in lines {
+            writeln!(file, "{}", line)?;
+        }
+        let file = file.into_inner().map_err(|e| e.into_error())?;
+        if let Ok(metadata) = fs::metadata(&path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.sync_all()?;
+        fs::rename(&temp, &path)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        fs::remove_file(&temp).ok();
+    }
+    result.with_context(|| format!("could not write to file `{:?}`", path))
+}
+
+/// The file completed tasks of `path` are archived to, `<name>-archive.md`
+/// next to it.
+pub fn archive_path(path: &Path) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy();
+    path.with_file_name(format!("{}-archive.md", stem))
+}
+
+/// Copy the file at `path` to the same path with `.bak` appended.
+pub fn backup_file(path: &Path) -> Result<()> {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    fs::copy(&path, &backup)
+        .with_context(|| format!("could not back up file `{:?}` to `{:?}`", path, backup))?;
+    Ok(())
+}
+
+fn strip_marker(text: &mut String, marker: &str) -> bool {
+    match text.strip_suffix(marker) {
+        Some(rest) => {
+            *text = rest.trim_end().to_string();
+            true
+        }
+        None => false,
+    }
+}
+
+fn strip_field(text: &mut String, marker: &str) -> Option<String> {
+    let (rest, value) = text.rsplit_once(' ')?;
+    let rest = rest.trim_end().strip_suffix(marker)?;
+    let value = value.to_string();
+    *text = rest.trim_end().to_string();
+    Some(value)
+}
+
+fn strip_date(text: &mut String, marker: &str) -> Option<NaiveDate> {
+    let mut rest = text.clone();
+    let date = NaiveDate::parse_from_str(&strip_field(&mut rest, marker)?, DATE_FORMAT).ok()?;
+    *text = rest;
+    Some(date)
+}
+
+fn strip_repeat(text: &mut String) -> Option<Repeat> {
+    let mut rest = text.clone();
+    let repeat = parse_repeat(&strip_field(&mut rest, REPEAT_MARKER)?).ok()?;
+    *text = rest;
+    Some(repeat)
+}
+
+fn strip_template(text: &mut String) -> Option<String> {
+    let (rest, template) = text.strip_suffix(" -->")?.rsplit_once(" <!-- ")?;
+    let template = template.to_string();
+    *text = rest.trim_end().to_string();
+    Some(template)
+}
+
+/// Fill in the `{date}`, `{day}`, `{week}`, `{month}` and `{year}`
+/// placeholders of `template` for an occurrence on `date`. Returns `None`
+/// when the template has no placeholders.
+pub fn expand_template(template: &str, date: NaiveDate) -> Option<String> {
+    let placeholders = [
+        ("{date}", date.format(DATE_FORMAT).to_string()),
+        ("{day}", date.format("%A").to_string()),
+        ("{week}", date.format("%G-W%V").to_string()),
+        ("{month}", date.format("%B %Y").to_string()),
+        ("{year}", date.format("%Y").to_string()),
+    ];
+    if !placeholders.iter().any(|(p, _)| template.contains(p)) {
+        return None;
+    }
+    let mut text = template.to_string();
+    for (placeholder, value) in placeholders.iter() {
+        text = text.replace(placeholder, value);
+    }
+    Some(text)
+}
+
+fn strip_tag(text: &mut String) -> Option<String> {
+    let (rest, word) = text.rsplit_once(' ')?;
+    let tag = parse_tag(word.strip_prefix('#')?).ok()?;
+    *text = rest.trim_end().to_string();
+    Some(tag)
+}
+
+pub fn parse_tag(tag: &str) -> Result<String> {
+    let tag = tag.strip_prefix('#').unwrap_or(tag);
+    if tag.is_empty()
+        || tag.chars().all(|c| c.is_ascii_digit())
+        || !tag
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '/')
+    {
+        bail!("invalid tag `{}`", tag);
+    }
+    Ok(tag.to_string())
+}
+
+pub fn parse_priority(priority: &str) -> Result<Priority> {
+    match priority.to_lowercase().as_str() {
+        "high" | "1" => Ok(Priority::High),
+        "medium" | "2" => Ok(Priority::Medium),
+        "low" | "3" => Ok(Priority::Low),
+        _ => bail!(
+            "invalid priority `{}`, expected high, medium, low or 1 to 3",
+            priority
+        ),
+    }
+}
+
+pub fn parse_repeat(repeat: &str) -> Result<Repeat> {
+    let (every, unit) = match repeat.to_lowercase().as_str() {
+        "daily" => (1, RepeatUnit::Day),
+        "weekly" => (1, RepeatUnit::Week),
+        "monthly" => (1, RepeatUnit::Month),
+        "yearly" => (1, RepeatUnit::Year),
+        rule => {
+            let (every, unit) = rule.split_at(rule.len().saturating_sub(1));
+            let unit = match unit {
+                "d" => RepeatUnit::Day,
+                "w" => RepeatUnit::Week,
+                "m" => RepeatUnit::Month,
+                "y" => RepeatUnit::Year,
+                _ => bail!(
+                    "invalid repeat `{}`, expected daily, weekly, monthly, yearly or e.g. `2w`",
+                    repeat
+                ),
+            };
+            let every: u32 = every
+                .parse()
+                .ok()
+                .filter(|every| *every > 0)
+                .with_context(|| format!("invalid repeat `{}`", repeat))?;
+            (every, unit)
+        }
+    };
+    Ok(Repeat { every, unit })
+}
+
+/// The sigils that mark tags, priorities and due dates in quick capture.
+/// An empty date sigil reads bare date words such as `tomorrow`.
+#[derive(Clone, Debug)]
+pub struct CaptureGrammar {
+    tag: String,
+    priority: String,
+    date: String,
+}
+
+impl Default for CaptureGrammar {
+    fn default() -> Self {
+        CaptureGrammar {
+            tag: String::from("#"),
+            priority: String::from("!"),
+            date: String::new(),
+        }
+    }
+}
+
+impl CaptureGrammar {
+    pub fn new(tag: &str, priority: &str, date: &str) -> Result<CaptureGrammar> {
+        if tag.is_empty() || priority.is_empty() {
+            bail!("tag and priority sigils cannot be empty");
+        }
+        if let Some(sigil) = [tag, priority, date]
+            .into_iter()
+            .find(|s| s.contains(char::is_whitespace))
+        {
+            bail!("sigil `{}` cannot contain whitespace", sigil);
+        }
+        if tag == priority || tag == date || priority == date {
+            bail!("tag, priority and date sigils must differ");
+        }
+        Ok(CaptureGrammar {
+            tag: tag.to_string(),
+            priority: priority.to_string(),
+            date: date.to_string(),
+        })
+    }
+}
+
+/// Read a task typed on one line with inline shorthand, by default `#tag`,
+/// a priority such as `!high` or `!1`, and a due date such as `tomorrow`,
+/// `friday`, `+3d` or `2024-07-01`. Every other word is the task text.
+pub fn parse_capture(text: &str, grammar: &CaptureGrammar) -> Result<Task> {
+    let mut task = Task::new(0, String::new(), TaskStatus::Incomplete);
+    let mut words = vec![];
+    for word in text.split_whitespace() {
+        if let Some(tag) = word
+            .strip_prefix(grammar.tag.as_str())
+            .and_then(|t| parse_tag(t).ok())
+        {
+            if !task.tags.contains(&tag) {
+                task.tags.push(tag);
+            }
+        } else if let Some(priority) = word
+            .strip_prefix(grammar.priority.as_str())
+            .and_then(|p| parse_priority(p).ok())
+        {
+            task.priority = Some(priority);
+        } else if let Some(date) = word
+            .strip_prefix(grammar.date.as_str())
+            .and_then(capture_date)
+        {
+            if task.due.replace(date).is_some() {
+                bail!("found more than one due date in `{}`", text);
+            }
+        } else {
+            words.push(word);
+        }
+    }
+    if words.is_empty() {
+        bail!("no task text in `{}`", text);
+    }
+    task.task = words.join(" ");
+    Ok(task)
+}
+
+/// A due date written in quick capture. Day names mean the next such day,
+/// today included.
+fn capture_date(word: &str) -> Option<NaiveDate> {
+    let word = word.to_lowercase();
+    let today = Local::now().date_naive();
+    if word.len() > 3 {
+        if let Ok(day) = word.parse::<Weekday>() {
+            let ahead = day.days_since(today.weekday());
+            return Some(today + DateDuration::days(ahead.into()));
+        }
+    }
+    if word == "today" || word == "tomorrow" || word.starts_with(['+', '0', '1', '2']) {
+        return parse_date(&word).ok();
+    }
+    None
+}
+
+pub fn parse_date(date: &str) -> Result<NaiveDate> {
+    let today = Local::now().date_naive();
+    match date {
+        "today" => return Ok(today),
+        "tomorrow" => return Ok(today + DateDuration::days(1)),
+        "yesterday" => return Ok(today - DateDuration::days(1)),
+        _ => {}
+    }
+    if let Some(offset) = date.strip_prefix('+').or_else(|| date.strip_prefix('-')) {
+        let (amount, unit) = offset.split_at(offset.len().saturating_sub(1));
+        let amount: i64 = amount
+            .parse()
+            .with_context(|| format!("invalid relative date `{}`", date))?;
+        let days = match unit {
+            "d" => amount,
+            "w" => amount * 7,
+            _ => bail!(
+                "invalid relative date `{}`, expected a `d` or `w` suffix",
+                date
+            ),
+        };
+        return Ok(if date.starts_with('-') {
+            today - DateDuration::days(days)
+        } else {
+            today + DateDuration::days(days)
+        });
+    }
+    NaiveDate::parse_from_str(date, DATE_FORMAT)
+        .with_context(|| format!("invalid date `{}`, expected YYYY-MM-DD", date))
+}
+
+pub fn normalize(text: &str) -> Vec<char> {
+    text.to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .chars()
+        .collect()
+}
+
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != cb);
+            current.push(
+                (previous[j] + cost)
+                    .min(previous[j + 1] + 1)
+                    .min(current[j] + 1),
+            );
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+pub fn is_similar(a: &str, b: &str) -> bool {
+    let (a, b) = (normalize(a), normalize(b));
+    let longest = a.len().max(b.len());
+    longest == 0 || edit_distance(&a, &b) * 5 <= longest
+}
+
+/// The existing tag closest to `tag`, if any is only a typo away.
+pub fn suggest_tag<'a>(tag: &str, existing: &'a [String]) -> Option<&'a String> {
+    let tag: Vec<char> = tag.to_lowercase().chars().collect();
+    existing
+        .iter()
+        .map(|e| {
+            (
+                edit_distance(&tag, &e.to_lowercase().chars().collect::<Vec<char>>()),
+                e,
+            )
+        })
+        .filter(|(distance, _)| *distance <= (tag.len() / 3).max(1))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, e)| e)
+}
+
+pub fn hyperlink(url: &str, text: &str) -> String {
+    format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, text)
+}
+
+fn get_id(task: &str) -> Result<usize, ParseIntError> {
+    task.chars()
+        .skip(8)
+        .take_while(|c| c != &'*')
+        .collect::<String>()
+        .parse::<usize>()
+}
+
+pub fn get_lines(path: &PathBuf) -> Result<Vec<String>> {
+    Ok(BufReader::new(File::open(path)?)
+        .lines()
+        .collect::<Result<_, _>>()?)
+}
+
+/// Tasks in `sections`. Lines in the other sections are skipped without
+/// being parsed.
+pub fn get_tasks_in_sections(lines: Vec<String>, sections: Vec<TaskStatus>) -> Vec<Task> {
+    parse_document(lines, &sections).0
+}
+
+/// Split task file lines into tasks and segments. A task section runs
+/// from its header to the `---` after it, or to the next heading. Every
+/// other line is kept as text. Lines in sections missing from `sections`
+/// are dropped, so only pass a subset when the segments are not needed.
+fn parse_document(lines: Vec<String>, sections: &[TaskStatus]) -> (Vec<Task>, Vec<Segment>) {
+    let mut status: Option<TaskStatus> = None;
+    let mut day: Option<NaiveDate> = None;
+    let mut tasks: Vec<Task> = vec![];
+    let mut segments: Vec<Segment> = vec![];
+    // Notes only belong to a task when they directly follow it.
+    let mut in_task = false;
+    for line in lines {
+        if let Ok(s) = TaskStatus::try_from(&line) {
+            if !segments
+                .iter()
+                .any(|segment| matches!(segment, Segment::Section(section) if *section == s))
+            {
+                segments.push(Segment::Section(s));
+            }
+            status = Some(s);
+            day = None;
+            in_task = false;
+            continue;
+        }
+        if let Some(s) = status {
+            if let Some(date) = parse_journal_heading(&line) {
+                day = Some(date);
+                in_task = false;
+                continue;
+            }
+            // An indented `---` under a task is part of its notes, not the
+            // end of the section.
+            if let Some(note) = parse_note(&line).filter(|_| in_task) {
+                if let Some(task) = tasks.last_mut() {
+                    task.notes.push(note.to_string());
+                }
+                continue;
+            }
+            if line.trim() == "---" {
+                status = None;
+                continue;
+            }
+            if line.starts_with('#') {
+                status = None;
+            } else {
+                in_task = false;
+                if !sections.contains(&s) {
+                    continue;
+                }
+                if let Ok(mut task) = Task::try_from((line, s)) {
+                    if s == TaskStatus::Complete && day.is_some() {
+                        task.completed = day;
+                    }
+                    tasks.push(task);
+                    in_task = true;
+                }
+                continue;
+            }
+        }
+        match segments.last_mut() {
+            Some(Segment::Text(text)) => text.push(line),
+            _ => segments.push(Segment::Text(vec![line])),
+        }
+    }
+    add_missing_sections(&mut segments);
+    (tasks, segments)
+}
+
+/// Make sure all three task sections are written, each missing one next
+/// to the section before it.
+fn add_missing_sections(segments: &mut Vec<Segment>) {
+    let position = |segments: &[Segment], s: TaskStatus| {
+        segments
+            .iter()
+            .position(|segment| matches!(segment, Segment::Section(section) if *section == s))
+    };
+    let blank = || Segment::Text(vec![String::new()]);
+    if !segments
+        .iter()
+        .any(|segment| matches!(segment, Segment::Section(_)))
+    {
+        if let Some(Segment::Text(text)) = segments.last() {
+            if text.last().is_some_and(|l| !l.trim().is_empty()) {
+                segments.push(blank());
+            }
+        }
+        for (i, s) in TaskStatus::all().into_iter().enumerate() {
+            if i > 0 {
+                segments.push(blank());
+            }
+            segments.push(Segment::Section(s));
+        }
+        return;
+    }
+    let sections = TaskStatus::all();
+    for (i, s) in sections.iter().enumerate() {
+        if position(segments, *s).is_some() {
+            continue;
+        }
+        match i
+            .checked_sub(1)
+            .and_then(|p| position(segments, sections[p]))
+        {
+            Some(previous) => {
+                segments.splice(previous + 1..previous + 1, [blank(), Segment::Section(*s)]);
+            }
+            None => {
+                let next = segments
+                    .iter()
+                    .position(|segment| matches!(segment, Segment::Section(_)))
+                    .unwrap_or(segments.len());
+                segments.splice(next..next, [Segment::Section(*s), blank()]);
+            }
+        }
+    }
+}
+
+fn parse_note(line: &str) -> Option<&str> {
+    if line.trim().is_empty() || !line.starts_with([' ', '\t']) {
+        return None;
+    }
+    Some(
+        line.strip_prefix("  ")
+            .or_else(|| line.strip_prefix('\t'))
+            .unwrap_or(line)
+            .trim_end(),
+    )
+}
+
+fn parse_journal_heading(line: &str) -> Option<NaiveDate> {
+    let date = line.strip_prefix("#### ")?;
+    NaiveDate::parse_from_str(date.trim(), DATE_FORMAT).ok()
+}
+
+fn add_section(
+    mut lines: Vec<String>,
+    tasks: &[Task],
+    section: TaskStatus,
+    journal: bool,
+) -> Vec<String> {
+    lines.push(format!("### {}", section.to_string().to_uppercase()));
+    lines.push(String::from(""));
+    let mut filtered_tasks = tasks
+        .iter()
+        .filter(|e| e.task_status == section)
+        .collect::<Vec<&Task>>();
+    // Selected tasks are a queue worked through in order, so priority
+    // does not reorder them.
+    if section != TaskStatus::Selected {
+        filtered_tasks.sort_by_key(|t| std::cmp::Reverse(t.priority_rank()));
+    }
+    if journal && section == TaskStatus::Complete {
+        // Tasks checked before the journal was started have no day and
+        // stay above the first heading, newest days come first.
+        filtered_tasks.sort_by_key(|t| t.completed.map(std::cmp::Reverse));
+        let mut day = None;
+        for t in filtered_tasks {
+            if let Some(date) = t.completed.filter(|date| day != Some(*date)) {
+                day = Some(date);
+                if lines.last().is_some_and(|l| !l.is_empty()) {
+                    lines.push(String::from(""));
+                }
+                lines.push(format!("#### {}", date.format(DATE_FORMAT)));
+                lines.push(String::from(""));
+            }
+            push_task(&mut lines, t, false);
+        }
+        if lines.last().is_some_and(|l| !l.is_empty()) {
+            lines.push(String::from(""));
+        }
+    } else if !filtered_tasks.is_empty() {
+        for t in filtered_tasks {
+            push_task(&mut lines, t, true);
+        }
+        lines.push(String::from(""));
+    }
+    lines.push(String::from("---"));
+    lines
+}
+
+fn push_task(lines: &mut Vec<String>, task: &Task, done_marker: bool) {
+    lines.push(task.markdown(done_marker));
+    lines.extend(task.notes.iter().map(|note| format!("  {}", note)));
+}
+
+fn get_task_id(task: &str) -> Result<usize, ParseIntError> {
+    task.chars()
+        .skip(8)
+        .take_while(|e| e != &'*')
+        .collect::<String>()
+        .parse::<usize>()
+}
+
+pub fn get_next_id(lines: &[String]) -> usize {
+    match lines.iter().filter_map(|e| get_task_id(e).ok()).max() {
+        Some(i) => i + 1,
+        None => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(String::from).collect()
+    }
+
+    const FILE: &str = "### SELECTED
+
+- [ ] **2**: write tests ⏫
+
+---
+
+### INCOMPLETE
+
+- [ ] **0**: plain task
+- [ ] **1**: tagged task #work #home 🔽 📅 2023-08-01 🔗 https://example.com ⭐ 🔒
+
+---
+
+### COMPLETE
+
+- [x] **3**: done
+
+---";
+
+    #[test]
+    fn parses_plain_task() {
+        let task = Task::try_from((
+            String::from("- [ ] **4**: plain task"),
+            TaskStatus::Incomplete,
+        ))
+        .unwrap();
+        assert_eq!(task.id, 4);
+        assert_eq!(task.task, "plain task");
+        assert_eq!(task.task_status, TaskStatus::Incomplete);
+        assert!(task.tags.is_empty());
+        assert_eq!(task.priority, None);
+    }
+
+    #[test]
+    fn parses_trailing_metadata() {
+        let line = "- [ ] **1**: tagged task #work #home 🔽 📅 2023-08-01 🔗 https://example.com ↪ ⭐ 📌 🔒";
+        let task = Task::try_from((String::from(line), TaskStatus::Incomplete)).unwrap();
+        assert_eq!(task.task, "tagged task");
+        assert_eq!(task.tags, vec!["work", "home"]);
+        assert_eq!(task.priority, Some(Priority::Low));
+        assert_eq!(task.due, NaiveDate::from_ymd_opt(2023, 8, 1));
+        assert_eq!(task.url.as_deref(), Some("https://example.com"));
+        assert!(task.carried && task.starred && task.pinned && task.protected);
+        assert_eq!(task.to_markdown(), line);
+    }
+
+    #[test]
+    fn keeps_hash_inside_text() {
+        let line = "- [ ] **0**: fix issue #12 in parser";
+        let task = Task::try_from((String::from(line), TaskStatus::Incomplete)).unwrap();
+        assert_eq!(task.task, "fix issue #12 in parser");
+        assert!(task.tags.is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        for line in ["", "plain text", "- [ ] no id", "- [ ] **x**: bad id"] {
+            assert!(Task::try_from((String::from(line), TaskStatus::Incomplete)).is_err());
+        }
+        assert!(Task::try_from((String::from("- [ ] **0**: open"), TaskStatus::Complete)).is_err());
+    }
+
+    #[test]
+    fn reads_tasks_from_sections() {
+        let tasks = get_tasks_in_sections(lines(FILE), TaskStatus::all());
+        let ids: Vec<usize> = tasks.iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![2, 0, 1, 3]);
+        assert_eq!(tasks[0].task_status, TaskStatus::Selected);
+        assert_eq!(tasks[3].task_status, TaskStatus::Complete);
+
+        let tasks = get_tasks_in_sections(lines(FILE), vec![TaskStatus::Incomplete]);
+        assert!(tasks
+            .iter()
+            .all(|t| t.task_status == TaskStatus::Incomplete));
+        assert_eq!(tasks.len(), 2);
+    }
+
+    #[test]
+    fn writes_parsed_file_unchanged() {
+        let list = TaskList::parse(Path::new("markdone.md"), lines(FILE));
+        assert_eq!(list.to_lines(), lines(FILE));
+    }
+
+    #[test]
+    fn keeps_text_outside_sections() {
+        let file = "# Home\n\nSome notes about this list.\n\n### SELECTED\n\n---\n\n## Ideas\n\n- not a task\n\n### INCOMPLETE\n\n- [ ] **0**: open\n\n---\n\n### COMPLETE\n\n---\n\nFooter";
+        let mut list = TaskList::parse(Path::new("markdone.md"), lines(file));
+        assert_eq!(list.to_lines(), lines(file));
+        list.check(0).unwrap();
+        let written = list.to_lines();
+        assert_eq!(written[..12], lines(file)[..12]);
+        assert_eq!(written.last().unwrap(), "Footer");
+        assert!(written.contains(&String::from("## Ideas")));
+
+        let list = TaskList::parse(
+            Path::new("markdone.md"),
+            lines("# Title\n\n### COMPLETE\n\n---"),
+        );
+        assert_eq!(
+            list.to_lines(),
+            lines("# Title\n\n### SELECTED\n\n---\n\n### INCOMPLETE\n\n---\n\n### COMPLETE\n\n---")
+        );
+        let list = TaskList::parse(Path::new("markdone.md"), lines("# Title"));
+        assert_eq!(list.to_lines()[..3], lines("# Title\n\n### SELECTED"));
+    }
+
+    #[test]
+    fn writes_empty_sections() {
+        let list = TaskList::parse(Path::new("markdone.md"), vec![]);
+        assert_eq!(
+            list.to_lines(),
+            lines("### SELECTED\n\n---\n\n### INCOMPLETE\n\n---\n\n### COMPLETE\n\n---")
+        );
+    }
+
+    #[test]
+    fn adds_tasks_by_priority() {
+        let mut list = TaskList::parse(Path::new("markdone.md"), lines(FILE));
+        assert_eq!(list.next_id(), 4);
+        let task = list.add(String::from("urgent"), TaskStatus::Incomplete);
+        task.priority = Some(Priority::High);
+        assert_eq!(task.id, 4);
+        assert_eq!(task.created, Some(Local::now().date_naive()));
+        task.created = None;
+        list.add(String::from("normal"), TaskStatus::Incomplete)
+            .created = None;
+        assert_eq!(list.next_id(), 6);
+        let incomplete: Vec<String> = list
+            .to_lines()
+            .into_iter()
+            .skip_while(|l| l != "### INCOMPLETE")
+            .take(6)
+            .collect();
+        assert_eq!(
+            incomplete[2..],
+            [
+                "- [ ] **4**: urgent ⏫",
+                "- [ ] **5**: normal",
+                "- [ ] **0**: plain task",
+                "- [ ] **1**: tagged task #work #home 🔽 📅 2023-08-01 🔗 https://example.com ⭐ 🔒",
+            ]
+        );
+    }
+
+    #[test]
+    fn moves_and_checks_tasks() {
+        let mut list = TaskList::parse(Path::new("markdone.md"), lines(FILE));
+        list.check(0).unwrap();
+        assert_eq!(list.get(0).unwrap().task_status, TaskStatus::Complete);
+        assert!(list.check(0).is_err());
+        list.move_task(0, TaskStatus::Selected).unwrap();
+        assert_eq!(list.get(0).unwrap().task_status, TaskStatus::Selected);
+        assert!(list.check(9).is_err());
+        assert_eq!(list.remove(0).unwrap().id, 0);
+        assert!(list.get(0).is_err());
+        assert_eq!(list.len(), 3);
+    }
+
+    #[test]
+    fn keeps_selected_tasks_in_queue_order() {
+        let mut list = TaskList::parse(Path::new("markdone.md"), lines(FILE));
+        list.get_mut(1).unwrap().priority = Some(Priority::High);
+        list.move_task(1, TaskStatus::Selected).unwrap();
+        list.move_task(0, TaskStatus::Selected).unwrap();
+        let selected = |list: &TaskList| -> Vec<usize> {
+            list.iter()
+                .filter(|t| t.task_status == TaskStatus::Selected)
+                .map(|t| t.id)
+                .collect()
+        };
+        assert_eq!(selected(&list), vec![2, 1, 0]);
+        list.bump(0).unwrap();
+        assert_eq!(list.next_selected().unwrap().id, 0);
+        let reparsed = TaskList::parse(Path::new("markdone.md"), list.to_lines());
+        assert_eq!(selected(&reparsed), vec![0, 2, 1]);
+        assert!(list.bump(3).is_err());
+        let id = list.add("e".to_string(), TaskStatus::Selected).id;
+        assert_eq!(selected(&list), vec![0, 2, 1, id]);
+    }
+
+    #[test]
+    fn reorders_tasks_within_section() {
+        let file = "### INCOMPLETE\n\n- [ ] **0**: a\n- [ ] **1**: b\n- [ ] **2**: c\n- [ ] **3**: d ⏫\n\n---";
+        let mut list = TaskList::parse(Path::new("markdone.md"), lines(file));
+        let order = |list: &TaskList| -> Vec<usize> {
+            TaskList::parse(Path::new("markdone.md"), list.to_lines())
+                .iter()
+                .map(|t| t.id)
+                .collect()
+        };
+        assert_eq!(order(&list), vec![3, 0, 1, 2]);
+        list.reorder(2, Placement::Before(0)).unwrap();
+        assert_eq!(order(&list), vec![3, 2, 0, 1]);
+        list.reorder(2, Placement::After(1)).unwrap();
+        assert_eq!(order(&list), vec![3, 0, 1, 2]);
+        list.reorder(1, Placement::Up).unwrap();
+        list.reorder(2, Placement::Down).unwrap();
+        assert_eq!(order(&list), vec![3, 1, 0, 2]);
+        assert!(list.reorder(0, Placement::Top).is_err());
+        list.move_task(0, TaskStatus::Selected).unwrap();
+        assert!(list.reorder(1, Placement::Before(0)).is_err());
+    }
+
+    #[test]
+    fn saves_and_loads_file() {
+        let path = env::temp_dir().join(format!("markdone-test-{}.md", process::id()));
+        fs::write(&path, format!("{}\n", FILE)).unwrap();
+        let mut list = TaskList::load(&path).unwrap();
+        list.remove(1).unwrap();
+        list.save().unwrap();
+        let list = TaskList::load(&path).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).ok();
+        assert_eq!(list.iter().map(|t| t.id).collect::<Vec<usize>>(), [2, 0, 3]);
+        assert!(!contents.contains("tagged task"));
+        assert!(contents.ends_with("---\n"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refuses_to_save_after_symlink_changes() {
+        let dir = env::temp_dir().join(format!("markdone-link-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (first, second, link) = (dir.join("a.md"), dir.join("b.md"), dir.join("tasks.md"));
+        fs::write(&first, format!("{}\n", FILE)).unwrap();
+        fs::write(&second, "").unwrap();
+        std::os::unix::fs::symlink(&first, &link).unwrap();
+        let mut list = TaskList::load(&link).unwrap();
+        list.save().unwrap();
+        fs::remove_file(&link).unwrap();
+        std::os::unix::fs::symlink(&second, &link).unwrap();
+        let result = list.save();
+        let contents = fs::read_to_string(&second).unwrap();
+        fs::remove_dir_all(&dir).ok();
+        assert!(result.is_err());
+        assert_eq!(contents, "");
+    }
+
+    #[test]
+    fn replaces_longer_file_contents() {
+        let path = env::temp_dir().join(format!("markdone-write-{}.md", process::id()));
+        fs::write(
+            &path,
+            "a much longer line than the new contents\n".repeat(10),
+        )
+        .unwrap();
+        write_lines(&path, &lines("short\nlines")).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).ok();
+        assert_eq!(contents, "short\nlines\n");
+    }
+
+    #[test]
+    fn keeps_target_and_deadline_apart() {
+        let line = "- [ ] **3**: ship release 🎯 2024-06-07 📅 2024-06-14";
+        let task = Task::try_from((String::from(line), TaskStatus::Incomplete)).unwrap();
+        assert_eq!(task.task, "ship release");
+        assert_eq!(task.target, NaiveDate::from_ymd_opt(2024, 6, 7));
+        assert_eq!(task.due, NaiveDate::from_ymd_opt(2024, 6, 14));
+        assert!(task.missed_target() && task.is_overdue());
+        assert_eq!(task.to_markdown(), line);
+    }
+
+    #[test]
+    fn formats_dates_for_locale() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 5).unwrap();
+        assert_eq!(DateStyle::default().format(date), "2024-06-05");
+        let style = DateStyle::new("%A %-d. %B", "fi_FI", "sunday").unwrap();
+        assert_eq!(style.format(date), "keskiviikko 5. kesäkuu");
+        assert_eq!(
+            style.week_of(date),
+            NaiveDate::from_ymd_opt(2024, 6, 2).unwrap()
+        );
+        assert!(DateStyle::new("%Q", "en_US", "monday").is_err());
+        assert!(DateStyle::new(DATE_FORMAT, "xx_XX", "monday").is_err());
+    }
+
+    #[test]
+    fn parses_repeat_rules() {
+        let line = "- [ ] **5**: water plants 📅 2024-01-31 🔁 monthly";
+        let task = Task::try_from((String::from(line), TaskStatus::Incomplete)).unwrap();
+        assert_eq!(task.task, "water plants");
+        assert_eq!(task.repeat.unwrap().to_string(), "monthly");
+        assert_eq!(task.to_markdown(), line);
+        assert_eq!(parse_repeat("2w").unwrap().to_string(), "2w");
+        assert!(parse_repeat("0d").is_err());
+        assert!(parse_repeat("often").is_err());
+    }
+
+    #[test]
+    fn checking_repeating_task_adds_next() {
+        let file = "### INCOMPLETE\n\n- [ ] **0**: pay rent #home 📅 2024-01-31 🔁 monthly\n\n---";
+        let mut list = TaskList::parse(Path::new("markdone.md"), lines(file));
+        let next = list.check(0).unwrap().unwrap();
+        let task = list.get(next).unwrap();
+        assert_eq!(task.task_status, TaskStatus::Incomplete);
+        assert_eq!(task.due, NaiveDate::from_ymd_opt(2024, 2, 29));
+        assert_eq!(task.tags, vec!["home"]);
+        assert_eq!(list.get(0).unwrap().task_status, TaskStatus::Complete);
+        assert_eq!(
+            list.check(next - 1).unwrap_err().to_string(),
+            "cannot move task from section `Complete`"
+        );
+    }
+
+    #[test]
+    fn keeps_notes_under_tasks() {
+        let file = "### SELECTED\n\n---\n\n### INCOMPLETE\n\n- [ ] **0**: plan trip\n  book hotel\n    near the station\n- [ ] **1**: no notes\n\n---\n\n### COMPLETE\n\n---";
+        let list = TaskList::parse(Path::new("markdone.md"), lines(file));
+        assert_eq!(
+            list.get(0).unwrap().notes,
+            vec!["book hotel", "  near the station"]
+        );
+        assert!(list.get(1).unwrap().notes.is_empty());
+        assert_eq!(list.to_lines(), lines(file));
+    }
+
+    #[test]
+    fn merges_notes_of_duplicates() {
+        let file = "### SELECTED\n\n---\n\n### INCOMPLETE\n\n- [ ] **0**: plan trip ➕ 2024-06-03\n  book hotel\n- [ ] **1**: plan the trip #travel ➕ 2024-06-01\n  pack bags\n  buy tickets\n\n---\n\n### COMPLETE\n\n---";
+        let mut list = TaskList::parse(Path::new("markdone.md"), lines(file));
+        list.merge(0, &[1], false).unwrap();
+        assert_eq!(list.len(), 1);
+        let task = list.get(0).unwrap();
+        assert_eq!(task.notes, vec!["book hotel", "pack bags", "buy tickets"]);
+        assert_eq!(task.tags, vec!["travel"]);
+        assert_eq!(task.created, NaiveDate::from_ymd_opt(2024, 6, 1));
+    }
+
+    #[test]
+    fn keeps_rule_inside_notes() {
+        let file = "### SELECTED\n\n---\n\n### INCOMPLETE\n\n- [ ] **0**: draft post\n  intro\n  ---\n  outro\n- [ ] **1**: after the rule\n\n---\n\n### COMPLETE\n\n---";
+        let list = TaskList::parse(Path::new("markdone.md"), lines(file));
+        assert_eq!(list.get(0).unwrap().notes, vec!["intro", "---", "outro"]);
+        assert_eq!(list.get(1).unwrap().task_status, TaskStatus::Incomplete);
+        assert_eq!(list.to_lines(), lines(file));
+    }
+
+    #[test]
+    fn groups_journal_by_day() {
+        let file = "### SELECTED\n\n---\n\n### INCOMPLETE\n\n---\n\n### COMPLETE\n\n- [x] **0**: old\n\n#### 2024-06-03\n\n- [x] **2**: later\n\n#### 2024-06-01\n\n- [x] **1**: earlier\n\n---";
+        let list = TaskList::parse(Path::new("markdone.md"), lines(file));
+        assert!(list.is_journal());
+        assert_eq!(list.get(0).unwrap().completed, None);
+        assert_eq!(
+            list.get(1).unwrap().completed,
+            NaiveDate::from_ymd_opt(2024, 6, 1)
+        );
+        assert_eq!(list.to_lines(), lines(file));
+
+        let mut list = TaskList::parse(Path::new("markdone.md"), lines(FILE));
+        assert!(!list.is_journal());
+        list.check(0).unwrap();
+        assert!(!list.to_lines().iter().any(|l| l.starts_with("####")));
+        list.set_journal(true);
+        let today = format!("#### {}", Local::now().date_naive().format(DATE_FORMAT));
+        assert!(list.to_lines().contains(&today));
+    }
+
+    #[test]
+    fn expands_templates_for_next_occurrence() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 7).unwrap();
+        assert_eq!(
+            expand_template("timesheet {week} ({day} {date})", date).unwrap(),
+            "timesheet 2024-W23 (Friday 2024-06-07)"
+        );
+        assert_eq!(expand_template("no placeholders", date), None);
+        let file = "### INCOMPLETE\n\n- [ ] **0**: report May 2024 📅 2024-05-31 🔁 monthly <!-- report {month} -->\n\n---";
+        let mut list = TaskList::parse(Path::new("markdone.md"), lines(file));
+        assert_eq!(list.get(0).unwrap().to_markdown(), lines(file)[2]);
+        let next = list.check(0).unwrap().unwrap();
+        assert_eq!(list.get(next).unwrap().task, "report June 2024");
+    }
+
+    #[test]
+    fn records_changed_day() {
+        let line = "- [ ] **3**: call bank #home ➕ 2024-06-01 ✏ 2024-06-05";
+        let task = Task::try_from((String::from(line), TaskStatus::Incomplete)).unwrap();
+        assert_eq!(task.task, "call bank");
+        assert_eq!(task.tags, vec!["home"]);
+        assert_eq!(task.updated, NaiveDate::from_ymd_opt(2024, 6, 5));
+        assert_eq!(task.to_markdown(), line);
+
+        let today = NaiveDate::from_ymd_opt(2024, 6, 10).unwrap();
+        let mut list = TaskList::parse(Path::new("markdone.md"), lines(FILE));
+        list.get_mut(1).unwrap().priority = Some(Priority::High);
+        list.move_task(0, TaskStatus::Selected).unwrap();
+        let added = list.add("new".to_string(), TaskStatus::Incomplete).id;
+        list.stamp_changed(today);
+        let updated: Vec<usize> = list
+            .iter()
+            .filter(|t| t.updated == Some(today))
+            .map(|t| t.id)
+            .collect();
+        assert_eq!(updated.len(), 2);
+        assert!(updated.contains(&0) && updated.contains(&1));
+        assert_eq!(list.get(added).unwrap().updated, None);
+    }
+
+    #[test]
+    fn records_authors() {
+        let line = "- [x] **5**: ship it ➕ 2024-06-01 👤 alice ✅ 2024-06-03 🏁 bob";
+        let task = Task::try_from((String::from(line), TaskStatus::Complete)).unwrap();
+        assert_eq!(task.task, "ship it");
+        assert_eq!(task.added_by.as_deref(), Some("alice"));
+        assert_eq!(task.checked_by.as_deref(), Some("bob"));
+        assert_eq!(task.to_markdown(), line);
+
+        let mut list = TaskList::parse(Path::new("markdone.md"), lines(FILE));
+        list.set_author(Some(String::from("carol")));
+        let id = list.add("review".to_string(), TaskStatus::Incomplete).id;
+        assert_eq!(list.get(id).unwrap().added_by.as_deref(), Some("carol"));
+        list.check(id).unwrap();
+        assert_eq!(list.get(id).unwrap().checked_by.as_deref(), Some("carol"));
+        list.move_task(id, TaskStatus::Incomplete).unwrap();
+        assert_eq!(list.get(id).unwrap().checked_by, None);
+    }
+
+    #[test]
+    fn records_added_selected_and_checked_days() {
+        let line = "- [x] **7**: done ⏫ ➕ 2024-06-01 🛫 2024-06-02 ✅ 2024-06-03";
+        let task = Task::try_from((String::from(line), TaskStatus::Complete)).unwrap();
+        assert_eq!(task.task, "done");
+        assert_eq!(task.created, NaiveDate::from_ymd_opt(2024, 6, 1));
+        assert_eq!(task.selected, NaiveDate::from_ymd_opt(2024, 6, 2));
+        assert_eq!(task.completed, NaiveDate::from_ymd_opt(2024, 6, 3));
+        assert_eq!(task.to_markdown(), line);
+
+        let mut list = TaskList::parse(Path::new("markdone.md"), lines(FILE));
+        list.check(0).unwrap();
+        assert_eq!(
+            list.get(0).unwrap().completed,
+            Some(Local::now().date_naive())
+        );
+        list.move_task(0, TaskStatus::Selected).unwrap();
+        assert_eq!(list.get(0).unwrap().completed, None);
+        assert_eq!(
+            list.get(0).unwrap().selected,
+            Some(Local::now().date_naive())
+        );
+        list.move_task(0, TaskStatus::Incomplete).unwrap();
+        assert_eq!(list.get(0).unwrap().selected, None);
+    }
+
+    #[test]
+    fn parses_dates() {
+        let today = Local::now().date_naive();
+        assert_eq!(parse_date("today").unwrap(), today);
+        assert_eq!(parse_date("+2d").unwrap(), today + DateDuration::days(2));
+        assert_eq!(parse_date("-1w").unwrap(), today - DateDuration::days(7));
+        assert_eq!(
+            parse_date("2023-08-01").unwrap(),
+            NaiveDate::from_ymd_opt(2023, 8, 1).unwrap()
+        );
+        assert!(parse_date("+2m").is_err());
+        assert!(parse_date("next week").is_err());
+    }
+
+    #[test]
+    fn parses_quick_capture() {
+        let today = Local::now().date_naive();
+        let parse_capture = |text| parse_capture(text, &CaptureGrammar::default());
+        let task = parse_capture("call bob tomorrow 3pm #phone !high").unwrap();
+        assert_eq!(task.task, "call bob 3pm");
+        assert_eq!(task.tags, vec!["phone"]);
+        assert_eq!(task.priority, Some(Priority::High));
+        assert_eq!(task.due, Some(today + DateDuration::days(1)));
+
+        let task = parse_capture("fix #12 on Friday").unwrap();
+        assert_eq!(task.task, "fix #12 on");
+        let due = task.due.unwrap();
+        assert_eq!(due.weekday(), Weekday::Fri);
+        assert!(due >= today && due < today + DateDuration::days(7));
+
+        assert_eq!(parse_capture("buy 2 apples").unwrap().task, "buy 2 apples");
+        assert!(parse_capture("#home !low").is_err());
+        assert!(parse_capture("pay rent today 2024-07-01").is_err());
+    }
+
+    #[test]
+    fn parses_quick_capture_with_custom_grammar() {
+        let grammar = CaptureGrammar::new("+", "p:", "@").unwrap();
+        let task = parse_capture("email #3 tomorrow +work p:2 @+2d", &grammar).unwrap();
+        assert_eq!(task.task, "email #3 tomorrow");
+        assert_eq!(task.tags, vec!["work"]);
+        assert_eq!(task.priority, Some(Priority::Medium));
+        assert_eq!(
+            task.due,
+            Some(Local::now().date_naive() + DateDuration::days(2))
+        );
+        assert!(CaptureGrammar::new("", "!", "").is_err());
+        assert!(CaptureGrammar::new("#", "#", "").is_err());
+        assert!(CaptureGrammar::new("#", "!", "due ").is_err());
+    }
+
+    #[test]
+    fn validates_tags_and_priorities() {
+        assert_eq!(parse_tag("#work").unwrap(), "work");
+        assert_eq!(parse_tag("area/sub-area").unwrap(), "area/sub-area");
+        assert!(parse_tag("12").is_err());
+        assert!(parse_tag("two words").is_err());
+        assert_eq!(parse_priority("HIGH").unwrap(), Priority::High);
+        assert_eq!(parse_priority("3").unwrap(), Priority::Low);
+        assert!(parse_priority("urgent").is_err());
+    }
+
+    #[test]
+    fn suggests_close_tags() {
+        let existing = vec![String::from("backend"), String::from("docs")];
+        assert_eq!(suggest_tag("bakend", &existing).unwrap(), "backend");
+        assert_eq!(suggest_tag("Doc", &existing).unwrap(), "docs");
+        assert_eq!(suggest_tag("frontend", &existing), None);
+    }
+
+    #[test]
+    fn detects_similar_tasks() {
+        assert!(is_similar("Write the report", "write the  report!"));
+        assert!(is_similar("write the report", "write the reports"));
+        assert!(!is_similar("write the report", "read the paper"));
+    }
+}
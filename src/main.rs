@@ -1,62 +1,555 @@
 use anyhow::{anyhow, bail, Context, Result};
-use clap::{Parser, Subcommand};
+use chrono::{DateTime, Datelike, Local, NaiveDate};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{self, Clear, ClearType},
+};
+use markdone::{
+    archive_path, backup_file, expand_template, get_lines, hyperlink, is_similar, normalize,
+    parse_capture, parse_date, parse_priority, parse_repeat, parse_tag, suggest_tag, write_lines,
+    CaptureGrammar, DateStyle, Placement, Priority, Repeat, RepeatUnit, Task, TaskList, TaskStatus,
+    DATE_FORMAT, MARKERS,
+};
+use regex::Regex;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use sha2::{Digest, Sha256};
 use std::{
-    fmt::Display,
-    fs::{File, OpenOptions},
-    io::{stdout, BufRead, BufReader, Seek, SeekFrom, Write},
-    num::ParseIntError,
-    path::PathBuf,
+    collections::BTreeMap,
+    env,
+    fs::{self, OpenOptions},
+    io::{stderr, stdin, stdout, IsTerminal, Read, Write},
+    mem,
+    ops::RangeInclusive,
+    path::{Component, Path, PathBuf},
+    process, thread,
+    time::{Duration, Instant, SystemTime},
 };
 
+mod tui;
+
 const DEFAULT_TASK_FILE: &str = "markdone.md";
+const CONFIG_FILE: &str = ".markdone.toml";
+const LINT_RULES: [&str; 4] = [
+    "high-priority-no-due",
+    "long-text",
+    "too-many-selected",
+    "forbidden-words",
+];
+const PROJECTS_FILE: &str = "projects.toml";
+const GLOBAL_CONFIG_FILE: &str = "config.toml";
+const HISTORY_DIR: &str = ".markdone/history";
+const FOCUS_DIR: &str = ".markdone/focus";
+const DEFAULT_HISTORY_LIMIT: usize = 20;
+const BUNDLE_MANIFEST: &str = "MANIFEST";
+const BUNDLE_TASKS: &str = "tasks.md";
+const BUNDLE_ARCHIVE: &str = "archive.md";
+const BUNDLE_CONFIG: &str = "config.toml";
+const BUNDLE_PROJECTS: &str = "projects.toml";
 
 #[derive(Debug, Parser)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
-    #[clap(short, long, help = "Enable quiet mode")]
+    #[clap(
+        short,
+        long,
+        help = "Don't print success messages or table headers, warnings are still printed"
+    )]
     quiet: bool,
-    #[clap(short, long, help = "Specify task file")]
+    #[clap(long, help = "Don't print warnings")]
+    no_warnings: bool,
+    #[clap(
+        long,
+        help = "Only print data and errors, same as `--quiet --no-warnings`"
+    )]
+    silent: bool,
+    #[clap(
+        short,
+        long,
+        help = "Specify task file, defaults to $MARKDONE_FILE or markdone.md"
+    )]
     file: Option<PathBuf>,
+    #[clap(long, help = "Refuse to run commands that modify the task file")]
+    read_only: bool,
+    #[clap(
+        long,
+        conflicts_with = "file",
+        help = "Use the task file registered for a project, defaults to $MARKDONE_PROJECT"
+    )]
+    project: Option<String>,
+    #[clap(long, help = "Copy the task file to a .bak file before changing it")]
+    backup: bool,
+    #[clap(
+        long,
+        help = "Follow a task file symlink even if it leads outside the project directory"
+    )]
+    allow_symlink: bool,
+    #[clap(long, help = "Never print terminal hyperlinks")]
+    no_hyperlinks: bool,
+    #[clap(long, value_enum, default_value_t = OutputMode::Table, help = "Style of task output")]
+    output: OutputMode,
+    #[clap(
+        long,
+        global = true,
+        value_enum,
+        help = "When to color task output, defaults to auto"
+    )]
+    color: Option<ColorChoice>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+enum ColorChoice {
+    /// Color output when printing to a terminal that supports it
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum OutputMode {
+    /// Tab separated columns
+    Table,
+    /// Fully labeled lines without layout tricks
+    ScreenReader,
 }
 
 #[derive(Debug, Subcommand)]
 enum Commands {
     /// Add new task to task list
     Add(AddOptions),
+    /// Add a task from one line, e.g. `call bob tomorrow #phone !high`
+    #[command(name = "q")]
+    Quick {
+        /// Task text with inline `#tags`, a `!priority` and a due date such
+        /// as `tomorrow`, `friday`, `+3d` or `2024-07-01`, the sigils can be
+        /// changed in the `[capture]` config
+        #[clap(required = true)]
+        text: Vec<String>,
+    },
     /// Mark task as complete
     Check {
-        /// Task ID
-        id: usize,
+        /// Task IDs or ranges such as `7-10`
+        #[clap(value_parser = parse_id_range, required_unless_present = "pattern")]
+        ids: Vec<RangeInclusive<usize>>,
+        #[clap(
+            long = "match",
+            conflicts_with = "ids",
+            help = "Check the only open task whose text contains this text"
+        )]
+        pattern: Option<String>,
     },
     /// Create new task list
     Create(CreateOptions),
+    /// Answer a few questions to create a config file and task file
+    Setup {
+        #[clap(long, help = "Replace an existing config file")]
+        force: bool,
+    },
     /// Show tasks from task list
     List(ListOptions),
+    /// Show tasks and show them again whenever the task file changes
+    Watch(WatchOptions),
+    /// Show tasks using a view defined in the config file
+    View {
+        /// Name of view
+        name: String,
+        #[clap(long, value_enum, help = "Output format")]
+        format: Option<ListFormat>,
+    },
     /// Mark task as selected
     Select {
+        /// Task IDs or ranges such as `7-10`
+        #[clap(value_parser = parse_id_range, required = true)]
+        ids: Vec<RangeInclusive<usize>>,
+    },
+    /// Check the task at the front of the selected queue
+    Pop,
+    /// Move a selected task to the front of the selected queue
+    Bump {
         /// Task ID
+        #[clap(value_parser = parse_id)]
         id: usize,
     },
+    /// Print the task at the front of the selected queue
+    Next,
+    /// Reorder a task within its section
+    Move(MoveOptions),
     /// Mark task as incomplete
     Uncheck(UncheckOptions),
     /// Deselect a selected task
-    Deselect { id: usize },
+    Deselect {
+        /// Task IDs or ranges such as `7-10`
+        #[clap(value_parser = parse_id_range, required = true)]
+        ids: Vec<RangeInclusive<usize>>,
+    },
+    /// Set the notes written under a task
+    Note {
+        /// Id of task
+        #[clap(value_parser = parse_id)]
+        id: usize,
+        /// Note text, an empty string removes the notes
+        #[clap(required_unless_present = "editor")]
+        text: Option<String>,
+        #[clap(
+            short,
+            long,
+            conflicts_with = "text",
+            help = "Edit the notes in $EDITOR"
+        )]
+        editor: bool,
+    },
+    /// Print the tasks referenced as `#12` or `PROJ-012` in text
+    Resolve {
+        /// Text with task references, read from stdin when left out
+        text: Vec<String>,
+        #[clap(long, help = "Print a JSON array of the referenced tasks")]
+        json: bool,
+    },
+    /// Print a task with all its details and notes
+    Show {
+        /// Id of task
+        #[clap(value_parser = parse_id)]
+        id: usize,
+    },
     /// Edit a task
     Edit {
         /// Id of task
+        #[clap(value_parser = parse_id)]
         id: usize,
         /// Updated task text
-        task: String,
+        #[clap(required_unless_present = "editor")]
+        task: Option<String>,
+        #[clap(
+            short,
+            long,
+            conflicts_with = "task",
+            help = "Edit task text in $EDITOR"
+        )]
+        editor: bool,
     },
     /// Delete a task
     Delete {
-        /// Id of task
+        /// Task IDs or ranges such as `7-10`
+        #[clap(value_parser = parse_id_range, required_unless_present = "pattern")]
+        ids: Vec<RangeInclusive<usize>>,
+        #[clap(
+            long = "match",
+            conflicts_with = "ids",
+            help = "Delete the only task whose text contains this text"
+        )]
+        pattern: Option<String>,
+        #[clap(long, help = "Delete task even if it is protected")]
+        force: bool,
+        #[clap(short, long, help = "Delete several tasks without asking first")]
+        yes: bool,
+    },
+    /// Import tasks exported from another tool
+    Import(ImportOptions),
+    /// Write all tasks in a format other tools can read
+    Export(ExportOptions),
+    /// Pack the task file, archive, history and config into one file
+    Bundle {
+        #[command(subcommand)]
+        command: BundleCommand,
+    },
+    /// Run a focus timer for a task
+    Focus(FocusOptions),
+    /// Protect a task from being deleted
+    Protect {
+        /// Task ID
+        #[clap(value_parser = parse_id)]
+        id: usize,
+    },
+    /// Remove protection from a task
+    Unprotect {
+        /// Task ID
+        #[clap(value_parser = parse_id)]
+        id: usize,
+    },
+    /// Pin a task to the top of listings
+    Pin {
+        /// Task ID
+        #[clap(value_parser = parse_id)]
         id: usize,
     },
+    /// Unpin a pinned task
+    Unpin {
+        /// Task ID
+        #[clap(value_parser = parse_id)]
+        id: usize,
+    },
+    /// Star a task
+    Star {
+        /// Task ID
+        #[clap(value_parser = parse_id)]
+        id: usize,
+    },
+    /// Remove star from a task
+    Unstar {
+        /// Task ID
+        #[clap(value_parser = parse_id)]
+        id: usize,
+    },
+    /// Open the URL of a task
+    Open {
+        /// Task ID
+        #[clap(value_parser = parse_id)]
+        id: usize,
+    },
+    /// Add a tag to a task
+    Tag {
+        /// Task ID
+        #[clap(value_parser = parse_id)]
+        id: usize,
+        /// Tag name
+        #[clap(value_parser = parse_tag)]
+        name: String,
+    },
+    /// Remove a tag from a task
+    Untag {
+        /// Task ID
+        #[clap(value_parser = parse_id)]
+        id: usize,
+        /// Tag name
+        #[clap(value_parser = parse_tag)]
+        name: String,
+    },
+    /// Change the priority of a task
+    Priority {
+        /// Task ID
+        #[clap(value_parser = parse_id)]
+        id: usize,
+        /// Priority level: high, medium, low, 1 to 3, or none to clear it
+        level: String,
+    },
+    /// Split a task into smaller tasks
+    Split {
+        /// Task ID
+        #[clap(value_parser = parse_id)]
+        id: usize,
+        /// Text of the new tasks
+        #[clap(required = true)]
+        parts: Vec<String>,
+    },
+    /// Merge duplicate tasks into the oldest one
+    MergeTasks {
+        /// Task IDs
+        #[clap(num_args = 2.., required = true, value_parser = parse_id)]
+        ids: Vec<usize>,
+        #[clap(long, help = "Merge tasks even if they are protected")]
+        force: bool,
+    },
+    /// Open an interactive terminal interface
+    Tui,
+    /// Move complete tasks to the archive file
+    Archive {
+        #[clap(
+            long,
+            value_parser = parse_since,
+            help = "Only archive tasks completed on or before a date or e.g. `30d` or `4w` ago"
+        )]
+        older_than: Option<NaiveDate>,
+    },
+    /// Manage the task files registered as projects
+    Project {
+        #[command(subcommand)]
+        command: ProjectCommand,
+    },
+    /// Find tasks whose text matches a pattern
+    Search(SearchOptions),
+    /// Revert the last command that changed the task file
+    Undo,
+    /// Reapply the last change reverted by undo
+    Redo,
+    /// Check tasks against the lint rules in the config
+    Lint,
+    /// Show the task file, section counts, the next selected tasks and recent activity
+    Status,
+    /// Show counts, completion history and the oldest open tasks
+    Stats(StatsOptions),
+    /// Show tasks completed per day over the last year as a heatmap
+    Heatmap,
+    /// Print reports over the task file or the registered projects
+    Report {
+        #[command(subcommand)]
+        command: ReportCommand,
+    },
+    /// Time parsing, listing and changing a generated task file
+    Bench(BenchOptions),
+    /// Check the task file for problems left behind by merges
+    Conflicts,
+    /// Write an SVG badge showing open and done task counts
+    Badge {
+        #[clap(long, help = "File to write the badge to, defaults to stdout")]
+        output: Option<PathBuf>,
+    },
+    /// Print shell functions and key bindings to load in a shell's rc file
+    ShellInit {
+        #[clap(value_enum)]
+        shell: Shell,
+    },
+    /// Print the JSON Schema of the tasks printed by `list --format json`
+    Schema,
+    /// Print a completion script for a shell, including task id completion
+    Completions {
+        #[clap(value_enum)]
+        shell: clap_complete::Shell,
+    },
+    /// Print task ids and text for shell completion scripts
+    #[clap(hide = true)]
+    CompleteIds {
+        #[clap(long, value_enum, default_value_t = CompletionStatus::Open)]
+        status: CompletionStatus,
+    },
+    /// Move unfinished selected tasks back to incomplete at the end of the day
+    Rollover {
+        #[clap(long, help = "Select tasks carried over by the last rollover again")]
+        restore: bool,
+    },
+    /// Run `markdone-<name>` from PATH for commands that are not built in
+    #[command(external_subcommand)]
+    External(Vec<String>),
 }
 
-#[derive(Debug, Parser)]
+impl Commands {
+    fn is_mutating(&self) -> bool {
+        match self {
+            Commands::List(_)
+            | Commands::Watch(_)
+            | Commands::View { .. }
+            | Commands::Search(_)
+            | Commands::Focus(_)
+            | Commands::Open { .. }
+            | Commands::ShellInit { .. }
+            | Commands::Schema
+            | Commands::Completions { .. }
+            | Commands::CompleteIds { .. }
+            | Commands::Project {
+                command: ProjectCommand::List,
+            }
+            | Commands::Lint
+            | Commands::Badge { .. }
+            | Commands::Export(_)
+            | Commands::Bundle {
+                command: BundleCommand::Export { .. },
+            }
+            | Commands::Show { .. }
+            | Commands::Resolve { .. }
+            | Commands::Next
+            | Commands::Stats(_)
+            | Commands::Heatmap
+            | Commands::Report { .. }
+            | Commands::Status
+            | Commands::Conflicts
+            | Commands::External(_)
+            | Commands::Bench(_) => false,
+            Commands::Add(_)
+            | Commands::Quick { .. }
+            | Commands::Check { .. }
+            | Commands::Create(_)
+            | Commands::Setup { .. }
+            | Commands::Select { .. }
+            | Commands::Pop
+            | Commands::Bump { .. }
+            | Commands::Move(_)
+            | Commands::Uncheck(_)
+            | Commands::Deselect { .. }
+            | Commands::Note { .. }
+            | Commands::Edit { .. }
+            | Commands::Delete { .. }
+            | Commands::Import(_)
+            | Commands::Bundle {
+                command: BundleCommand::Import { .. },
+            }
+            | Commands::Protect { .. }
+            | Commands::Unprotect { .. }
+            | Commands::Pin { .. }
+            | Commands::Unpin { .. }
+            | Commands::Star { .. }
+            | Commands::Unstar { .. }
+            | Commands::Tag { .. }
+            | Commands::Untag { .. }
+            | Commands::Priority { .. }
+            | Commands::Split { .. }
+            | Commands::MergeTasks { .. }
+            | Commands::Project {
+                command: ProjectCommand::Add { .. } | ProjectCommand::Remove { .. },
+            }
+            | Commands::Tui
+            | Commands::Archive { .. }
+            | Commands::Undo
+            | Commands::Redo
+            | Commands::Rollover { .. } => true,
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum ReportCommand {
+    /// Count incomplete tasks by how long ago they were added
+    Aging {
+        #[clap(long, default_value_t = 5, help = "Number of oldest tasks to list")]
+        oldest: usize,
+    },
+    /// Summarize each registered project on one line, least recently changed first
+    Rollup,
+}
+
+#[derive(Debug, Subcommand)]
+enum ProjectCommand {
+    /// Register a task file under a project name
+    Add {
+        /// Project name
+        name: String,
+        /// Task file of the project
+        file: PathBuf,
+    },
+    /// Show registered projects
+    List,
+    /// Forget a registered project, keeping its task file
+    Remove {
+        /// Project name
+        name: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum BundleCommand {
+    /// Write a bundle to a file
+    Export {
+        /// Bundle file, e.g. `markdone.tar.zst`
+        file: PathBuf,
+    },
+    /// Restore the files in a bundle after checking them against its manifest
+    Import {
+        /// Bundle file
+        file: PathBuf,
+        #[clap(long, help = "Replace files that already exist")]
+        force: bool,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum CompletionStatus {
+    /// Incomplete and selected tasks
+    Open,
+    /// Complete tasks
+    Complete,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Parser)]
+#[serde(default, deny_unknown_fields)]
 struct ListOptions {
     #[clap(short, long, help = "Show all tasks")]
     all: bool,
@@ -66,14 +559,480 @@ struct ListOptions {
     incomplete: bool,
     #[clap(short, long, help = "Only show complete")]
     complete: bool,
+    #[clap(long, help = "Only show starred tasks")]
+    starred: bool,
+    #[clap(long, conflicts_with = "starred", help = "Hide starred tasks")]
+    not_starred: bool,
+    #[clap(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        help = "Only show tasks with any of the given statuses"
+    )]
+    status: Vec<TaskStatus>,
+    #[clap(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        help = "Hide tasks with any of the given statuses"
+    )]
+    not_status: Vec<TaskStatus>,
+    #[clap(long, value_parser = parse_date, help = "Only show tasks due before date")]
+    #[serde(deserialize_with = "deserialize_date")]
+    due_before: Option<NaiveDate>,
+    #[clap(long, help = "Only show tasks that are past their due date")]
+    overdue: bool,
+    #[clap(long, help = "Only show open tasks that are past their target date")]
+    missed_target: bool,
+    #[clap(
+        long,
+        value_parser = parse_since,
+        help = "Only show tasks completed on or after a date, e.g. `yesterday` or `7d`"
+    )]
+    #[serde(deserialize_with = "deserialize_since")]
+    completed_since: Option<NaiveDate>,
+    #[clap(
+        long,
+        value_parser = parse_since,
+        help = "Only show tasks added, selected, completed or changed on or after a date, e.g. `2d`"
+    )]
+    #[serde(deserialize_with = "deserialize_since")]
+    modified_since: Option<NaiveDate>,
+    #[clap(long = "tag", value_parser = parse_tag, help = "Only show tasks with all given tags")]
+    tags: Vec<String>,
+    #[clap(long = "not-tag", value_parser = parse_tag, help = "Hide tasks with any given tag")]
+    not_tags: Vec<String>,
+    #[clap(long, help = "Only show tasks added or checked by an author")]
+    author: Option<String>,
+    #[clap(
+        long = "where",
+        value_parser = parse_expression,
+        help = "Only show tasks matching an expression, e.g. `status!=complete and (priority=high or due<+2d)`"
+    )]
+    #[serde(rename = "where", deserialize_with = "deserialize_expression")]
+    expression: Option<Expr>,
+    #[clap(
+        long = "priority",
+        value_parser = parse_priority,
+        help = "Only show tasks with any of the given priorities"
+    )]
+    priorities: Vec<Priority>,
+    #[clap(long, value_enum, help = "Sort tasks by field")]
+    sort: Option<SortKey>,
+    #[clap(long, value_enum, help = "Output format")]
+    format: Option<ListFormat>,
+    #[clap(long, help = "Only print the number of matching tasks")]
+    #[serde(skip)]
+    count_only: bool,
+    #[clap(
+        long,
+        help = "Print each task with a template, e.g. `{id}\\t{due}\\t{text}`"
+    )]
+    format_string: Option<String>,
+    #[clap(
+        long,
+        help = "Print the task file and a legend of markers above the list"
+    )]
+    legend: bool,
+    #[clap(long, help = "Show tasks from the archive file instead")]
+    archived: bool,
+    #[clap(
+        long,
+        conflicts_with = "archived",
+        help = "Show tasks from the archive file as well"
+    )]
+    include_archive: bool,
+    #[clap(long, help = "Show tasks from every registered project")]
+    all_projects: bool,
+    #[clap(skip)]
+    #[serde(skip)]
+    pattern: Option<Regex>,
+}
+
+#[derive(Debug, Parser)]
+struct BenchOptions {
+    #[clap(long, default_value_t = 10_000, help = "Number of tasks to generate")]
+    tasks: usize,
+    #[clap(long, default_value_t = 5, help = "Times to repeat each step")]
+    runs: usize,
+}
+
+#[derive(Debug, Parser)]
+struct WatchOptions {
+    #[clap(
+        long,
+        default_value_t = 500,
+        help = "Milliseconds between checks for changes"
+    )]
+    interval: u64,
+    #[clap(flatten)]
+    list: ListOptions,
+}
+
+#[derive(Debug, Parser)]
+struct SearchOptions {
+    /// Text to search for
+    query: String,
+    #[clap(long, help = "Treat the query as a regular expression")]
+    regex: bool,
+    #[clap(flatten)]
+    list: ListOptions,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+enum ListFormat {
+    /// Human readable table
+    Table,
+    /// JSON array of tasks
+    Json,
+    /// Tab separated values with a header row
+    Tsv,
+    /// Comma separated values with a header row
+    Csv,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+enum SortKey {
+    /// Earliest due date first, tasks without a due date last
+    Due,
+    /// Highest priority first
+    Priority,
+    /// Oldest task first, tasks without an added date last
+    Created,
+    /// Earliest completion first, open tasks last
+    Completed,
+}
+
+impl ListOptions {
+    fn has_filters(&self) -> bool {
+        self.all
+            || self.selected
+            || self.incomplete
+            || self.complete
+            || self.starred
+            || self.not_starred
+            || !self.status.is_empty()
+            || !self.not_status.is_empty()
+            || self.due_before.is_some()
+            || self.overdue
+            || self.missed_target
+            || self.completed_since.is_some()
+            || self.modified_since.is_some()
+            || !self.tags.is_empty()
+            || !self.not_tags.is_empty()
+            || self.author.is_some()
+            || self.expression.is_some()
+            || !self.priorities.is_empty()
+            || self.sort.is_some()
+    }
+
+    fn filter(&self) -> Filter {
+        let mut filter = Filter::default();
+        if self.selected {
+            filter.statuses.push(TaskStatus::Selected);
+        }
+        if self.incomplete {
+            filter.statuses.push(TaskStatus::Incomplete);
+        }
+        if self.complete {
+            filter.statuses.push(TaskStatus::Complete);
+        }
+        filter.statuses.extend(self.status.iter().cloned());
+        if self.all {
+            filter.statuses.clear();
+        }
+        filter.excluded_statuses = self.not_status.clone();
+        if self.starred {
+            filter.starred = Some(true);
+        }
+        if self.not_starred {
+            filter.starred = Some(false);
+        }
+        filter.tags = self.tags.clone();
+        filter.excluded_tags = self.not_tags.clone();
+        filter.author = self.author.clone();
+        filter.expression = self.expression.clone().map(Box::new);
+        filter.priorities = self.priorities.clone();
+        filter.due_before = self.due_before;
+        filter.completed_since = self.completed_since;
+        filter.modified_since = self.modified_since;
+        filter.pattern = self.pattern.clone();
+        if self.overdue {
+            let today = Local::now().date_naive();
+            filter.due_before = Some(filter.due_before.map_or(today, |d| d.min(today)));
+            filter.excluded_statuses.push(TaskStatus::Complete);
+        }
+        filter.missed_target = self.missed_target;
+        filter
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct Filter {
+    statuses: Vec<TaskStatus>,
+    excluded_statuses: Vec<TaskStatus>,
+    starred: Option<bool>,
+    tags: Vec<String>,
+    excluded_tags: Vec<String>,
+    author: Option<String>,
+    priorities: Vec<Priority>,
+    due_before: Option<NaiveDate>,
+    missed_target: bool,
+    completed_since: Option<NaiveDate>,
+    modified_since: Option<NaiveDate>,
+    pattern: Option<Regex>,
+    expression: Option<Box<Expr>>,
+}
+
+impl Filter {
+    fn sections(&self) -> Vec<TaskStatus> {
+        TaskStatus::all()
+            .into_iter()
+            .filter(|s| self.statuses.is_empty() || self.statuses.contains(s))
+            .filter(|s| !self.excluded_statuses.contains(s))
+            .collect()
+    }
+
+    fn matches(&self, task: &Task) -> bool {
+        (self.statuses.is_empty() || self.statuses.contains(&task.task_status))
+            && !self.excluded_statuses.contains(&task.task_status)
+            && self.starred.is_none_or(|starred| task.starred == starred)
+            && self.tags.iter().all(|tag| task.tags.contains(tag))
+            && !self.excluded_tags.iter().any(|tag| task.tags.contains(tag))
+            && self.author.as_ref().is_none_or(|author| {
+                task.added_by.as_ref() == Some(author) || task.checked_by.as_ref() == Some(author)
+            })
+            && (self.priorities.is_empty()
+                || task.priority.is_some_and(|p| self.priorities.contains(&p)))
+            && self
+                .due_before
+                .is_none_or(|date| task.due.is_some_and(|due| due < date))
+            && (!self.missed_target || task.missed_target())
+            && self
+                .completed_since
+                .is_none_or(|date| task.completed.is_some_and(|done| done >= date))
+            && self.modified_since.is_none_or(|date| {
+                [task.created, task.selected, task.completed, task.updated]
+                    .into_iter()
+                    .flatten()
+                    .any(|day| day >= date)
+            })
+            && self
+                .pattern
+                .as_ref()
+                .is_none_or(|pattern| pattern.is_match(&task.task))
+            && self.expression.as_ref().is_none_or(|e| e.matches(task))
+    }
+}
+
+/// A filter expression such as `status!=complete and (priority=high or
+/// due<+2d)`, with a `Filter` for each comparison in it.
+#[derive(Clone, Debug)]
+enum Expr {
+    Filter(Filter),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+}
+
+impl Expr {
+    fn matches(&self, task: &Task) -> bool {
+        match self {
+            Expr::Filter(filter) => filter.matches(task),
+            Expr::Not(expr) => !expr.matches(task),
+            Expr::And(exprs) => exprs.iter().all(|e| e.matches(task)),
+            Expr::Or(exprs) => exprs.iter().any(|e| e.matches(task)),
+        }
+    }
+}
+
+const EXPRESSION_FIELDS: &str = "status, priority, tag, author, starred, due, completed, modified";
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Operator(String),
+    Word(String),
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            c if c.is_whitespace() => {}
+            '=' | '!' | '<' | '>' => {
+                let mut operator = c.to_string();
+                while let Some(c) = chars.next_if(|c| matches!(c, '=' | '!' | '<' | '>')) {
+                    operator.push(c);
+                }
+                tokens.push(Token::Operator(operator));
+            }
+            c => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|c| {
+                    !c.is_whitespace() && !matches!(c, '(' | ')' | '=' | '!' | '<' | '>')
+                }) {
+                    word.push(c);
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    tokens
+}
+
+/// Parse a filter expression. Comparisons are joined with `and`, `or`
+/// and `not`, `and` binding tighter than `or`, and grouped with
+/// parentheses.
+fn parse_expression(text: &str) -> Result<Expr> {
+    let mut tokens = tokenize(text).into_iter().peekable();
+    let expr = parse_or(&mut tokens)?;
+    match tokens.next() {
+        None => Ok(expr),
+        Some(Token::Close) => bail!("unmatched `)` in `{}`", text),
+        Some(token) => bail!("expected `and` or `or`, found {:?} in `{}`", token, text),
+    }
+}
+
+type Tokens = std::iter::Peekable<std::vec::IntoIter<Token>>;
+
+fn is_keyword(token: Option<&Token>, keyword: &str) -> bool {
+    matches!(token, Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword))
+}
+
+fn parse_or(tokens: &mut Tokens) -> Result<Expr> {
+    let mut exprs = vec![parse_and(tokens)?];
+    while is_keyword(tokens.peek(), "or") {
+        tokens.next();
+        exprs.push(parse_and(tokens)?);
+    }
+    Ok(match exprs.len() {
+        1 => exprs.remove(0),
+        _ => Expr::Or(exprs),
+    })
+}
+
+fn parse_and(tokens: &mut Tokens) -> Result<Expr> {
+    let mut exprs = vec![parse_unary(tokens)?];
+    while is_keyword(tokens.peek(), "and") {
+        tokens.next();
+        exprs.push(parse_unary(tokens)?);
+    }
+    Ok(match exprs.len() {
+        1 => exprs.remove(0),
+        _ => Expr::And(exprs),
+    })
+}
+
+fn parse_unary(tokens: &mut Tokens) -> Result<Expr> {
+    if is_keyword(tokens.peek(), "not") {
+        tokens.next();
+        return Ok(Expr::Not(Box::new(parse_unary(tokens)?)));
+    }
+    match tokens.next() {
+        Some(Token::Open) => {
+            let expr = parse_or(tokens)?;
+            match tokens.next() {
+                Some(Token::Close) => Ok(expr),
+                _ => bail!("expected `)`"),
+            }
+        }
+        Some(Token::Word(field)) => match (tokens.next(), tokens.next()) {
+            (Some(Token::Operator(operator)), Some(Token::Word(value))) => {
+                parse_comparison(&field.to_lowercase(), &operator, &value)
+            }
+            _ => bail!("expected a comparison such as `{}=value`", field),
+        },
+        Some(token) => bail!("expected a comparison, found {:?}", token),
+        None => bail!("expected a comparison, found the end of the expression"),
+    }
+}
+
+/// One comparison of a task field with a value. Dates can be relative as
+/// in `--due-before` and `--completed-since`.
+fn parse_comparison(field: &str, operator: &str, value: &str) -> Result<Expr> {
+    let mut filter = Filter::default();
+    let day = chrono::Duration::days(1);
+    match (field, operator) {
+        ("status", "=" | "!=") => filter.statuses.push(
+            TaskStatus::from_str(value, true)
+                .map_err(|_| anyhow!("invalid section `{}`", value))?,
+        ),
+        ("priority", "=" | "!=") => filter.priorities.push(parse_priority(value)?),
+        ("tag", "=" | "!=") => filter.tags.push(parse_tag(value)?),
+        ("author", "=" | "!=") => filter.author = Some(value.to_string()),
+        ("starred", "=" | "!=") => {
+            filter.starred = Some(
+                value
+                    .parse()
+                    .map_err(|_| anyhow!("expected true or false, found `{}`", value))?,
+            )
+        }
+        ("due", "<") => filter.due_before = Some(parse_date(value)?),
+        ("due", "<=") => filter.due_before = Some(parse_date(value)? + day),
+        ("completed", ">=") => filter.completed_since = Some(parse_since(value)?),
+        ("completed", ">") => filter.completed_since = Some(parse_since(value)? + day),
+        ("modified", ">=") => filter.modified_since = Some(parse_since(value)?),
+        ("modified", ">") => filter.modified_since = Some(parse_since(value)? + day),
+        (field, operator) if EXPRESSION_FIELDS.split(", ").any(|f| f == field) => {
+            bail!("cannot compare `{}` with `{}`", field, operator)
+        }
+        (field, _) => bail!(
+            "unknown field `{}`, expected one of {}",
+            field,
+            EXPRESSION_FIELDS
+        ),
+    }
+    let expr = Expr::Filter(filter);
+    Ok(match operator {
+        "!=" => Expr::Not(Box::new(expr)),
+        _ => expr,
+    })
+}
+
+fn deserialize_expression<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Expr>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|text| parse_expression(&text).map_err(de::Error::custom))
+        .transpose()
+}
+
+/// Read the `[views]` config table, where a view is either a table of
+/// list options or an expression as for `list --where`.
+fn deserialize_views<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<String, ListOptions>, D::Error> {
+    let mut views = BTreeMap::new();
+    for (name, value) in BTreeMap::<String, toml::Value>::deserialize(deserializer)? {
+        let options =
+            match value {
+                toml::Value::String(text) => ListOptions {
+                    expression: Some(parse_expression(&text).map_err(|e| {
+                        de::Error::custom(format!("invalid view `{}`: {:#}", name, e))
+                    })?),
+                    ..ListOptions::default()
+                },
+                value => value.try_into().map_err(de::Error::custom)?,
+            };
+        views.insert(name, options);
+    }
+    Ok(views)
 }
 
 #[derive(Debug, Parser)]
 struct UncheckOptions {
     #[clap(short, long, help = "Select task")]
     select: bool,
-    /// Task ID
-    id: usize,
+    /// Task IDs or ranges such as `7-10`
+    #[clap(value_parser = parse_id_range, required = true)]
+    ids: Vec<RangeInclusive<usize>>,
 }
 
 #[derive(Debug, Parser)]
@@ -85,310 +1044,3037 @@ struct CreateOptions {
 #[derive(Debug, Parser)]
 struct AddOptions {
     /// Task text
-    task: String,
+    #[clap(required_unless_present_any = ["stdin", "from_file"])]
+    task: Option<String>,
+    #[clap(
+        long,
+        conflicts_with = "task",
+        help = "Add one task per line read from stdin, skipping similar tasks"
+    )]
+    stdin: bool,
+    #[clap(
+        long,
+        conflicts_with_all = ["task", "stdin"],
+        help = "Add each item of a markdown list or checkbox list in a file, skipping similar tasks"
+    )]
+    from_file: Option<PathBuf>,
     #[clap(short, long, help = "Select added task")]
     select: bool,
     #[clap(short, long, help = "Complete added task")]
     complete: bool,
+    #[clap(long, help = "Link added task to a URL")]
+    url: Option<String>,
+    #[clap(
+        long,
+        visible_alias = "deadline",
+        value_parser = parse_date,
+        help = "Hard deadline, e.g. `2024-07-01`, `tomorrow` or `+3d`"
+    )]
+    due: Option<NaiveDate>,
+    #[clap(
+        long,
+        value_parser = parse_date,
+        help = "Day to aim for, shown as a reminder once missed but not as overdue"
+    )]
+    target: Option<NaiveDate>,
+    #[clap(short, long = "tag", value_parser = parse_tag, help = "Tag added task")]
+    tags: Vec<String>,
+    #[clap(
+        short,
+        long,
+        value_parser = parse_priority,
+        help = "Priority of added task: high, medium, low or 1 to 3"
+    )]
+    priority: Option<Priority>,
+    #[clap(
+        long,
+        value_parser = parse_repeat,
+        help = "Add the task again when checked: daily, weekly, monthly, yearly or e.g. `2w`",
+        long_help = "Add the task again when checked: daily, weekly, monthly, yearly or e.g. `2w`. \
+            {date}, {day}, {week}, {month} and {year} in the task text are filled in with the \
+            due date of each occurrence"
+    )]
+    repeat: Option<Repeat>,
+    #[clap(long, help = "Add task even if a similar task exists")]
+    force: bool,
+    #[clap(long, help = "Only allow tags that are already used by other tasks")]
+    strict_tags: bool,
 }
 
-#[derive(Clone, Debug, PartialEq)]
-enum TaskStatus {
-    Selected,
-    Incomplete,
-    Complete,
+#[derive(Debug, Parser)]
+struct FocusOptions {
+    /// Task ID, defaults to the first selected task
+    #[clap(value_parser = parse_id)]
+    id: Option<usize>,
+    #[clap(
+        short,
+        long,
+        default_value_t = 25,
+        help = "Length of the session in minutes"
+    )]
+    minutes: u64,
+    #[clap(
+        long,
+        help = "Refuse changes from other markdone commands until the session ends"
+    )]
+    lock: bool,
 }
 
-impl Display for TaskStatus {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            TaskStatus::Selected => write!(f, "selected"),
-            TaskStatus::Incomplete => write!(f, "incomplete"),
-            TaskStatus::Complete => write!(f, "complete"),
-        }
-    }
+#[derive(Debug, Parser)]
+struct ImportOptions {
+    /// File to import tasks from
+    source: PathBuf,
+    #[clap(long, value_enum, help = "Format of the imported file")]
+    format: ImportFormat,
+    #[clap(
+        long,
+        value_parser = parse_column_map,
+        help = "Map task fields to CSV columns, e.g. `title=Name,status=Status,due=Due`",
+        long_help = "Map task fields to CSV columns, e.g. `title=Name,status=Status,due=Due`. \
+            The fields are title, status, due and priority. Without a mapping the title comes \
+            from the `Title` column and the status from the `Status` column if there is one"
+    )]
+    map: Option<ColumnMap>,
+    #[clap(
+        long,
+        value_parser = parse_list_map,
+        help = "Put open tasks from lists in sections, e.g. `Today=selected,Work=incomplete`"
+    )]
+    list_map: Option<ListMap>,
 }
 
-impl TaskStatus {
-    fn all() -> Vec<TaskStatus> {
-        return vec![
-            TaskStatus::Selected,
-            TaskStatus::Incomplete,
-            TaskStatus::Complete,
-        ];
-    }
+#[derive(Clone, Debug, ValueEnum)]
+enum ImportFormat {
+    /// Generic CSV or TSV file, see `--map`
+    Csv,
+    /// CSV or TSV export of a GitHub Projects view
+    GithubProjects,
+    /// CSV with Title, List, Completed, Due Date and Priority columns
+    AppleReminders,
+    /// JSON of Microsoft To Do lists or tasks as returned by Microsoft Graph
+    MicrosoftTodo,
+    /// Plain text file in the todo.txt format
+    Todotxt,
+    /// JSON written by `task export`
+    TaskwarriorJson,
 }
 
-impl TryFrom<&String> for TaskStatus {
-    type Error = anyhow::Error;
-    fn try_from(section: &String) -> std::result::Result<Self, Self::Error> {
-        if section == "### SELECTED" {
-            return Ok(TaskStatus::Selected);
-        }
-        if section == "### INCOMPLETE" {
-            return Ok(TaskStatus::Incomplete);
-        }
-        if section == "### COMPLETE" {
-            return Ok(TaskStatus::Complete);
+#[derive(Debug, Parser)]
+struct MoveOptions {
+    /// Task ID
+    #[clap(value_parser = parse_id)]
+    id: usize,
+    #[clap(flatten)]
+    placement: MovePlacement,
+}
+
+#[derive(Debug, Parser)]
+#[group(required = true, multiple = false)]
+struct MovePlacement {
+    #[clap(long, help = "Move the task up one place")]
+    up: bool,
+    #[clap(long, help = "Move the task down one place")]
+    down: bool,
+    #[clap(long, help = "Move the task to the top of its section")]
+    top: bool,
+    #[clap(long, help = "Move the task to the bottom of its section")]
+    bottom: bool,
+    #[clap(long, value_parser = parse_id, help = "Move the task just before another task")]
+    before: Option<usize>,
+    #[clap(long, value_parser = parse_id, help = "Move the task just after another task")]
+    after: Option<usize>,
+}
+
+impl MovePlacement {
+    fn placement(&self) -> Placement {
+        match (self.before, self.after) {
+            (Some(id), _) => Placement::Before(id),
+            (_, Some(id)) => Placement::After(id),
+            _ if self.up => Placement::Up,
+            _ if self.down => Placement::Down,
+            _ if self.top => Placement::Top,
+            _ => Placement::Bottom,
         }
-        return Err(anyhow!("Error: could not find status"));
     }
 }
 
-#[derive(Debug)]
-struct Task {
-    id: usize,
-    task: String,
-    task_status: TaskStatus,
+#[derive(Debug, Parser)]
+struct StatsOptions {
+    #[clap(
+        long,
+        value_parser = parse_since,
+        help = "Count tasks completed since a date or within e.g. `7d` or `4w`, defaults to 7d"
+    )]
+    since: Option<NaiveDate>,
+    #[clap(
+        long,
+        default_value_t = 5,
+        help = "Number of oldest open tasks to show"
+    )]
+    oldest: usize,
+    #[clap(long, help = "Count tasks from the archive file as well")]
+    include_archive: bool,
 }
 
-impl Task {
-    fn to_markdown(&self) -> String {
-        let completed = if let TaskStatus::Complete = self.task_status {
-            'x'
-        } else {
-            ' '
-        };
+#[derive(Debug, Parser)]
+struct ExportOptions {
+    #[clap(long, value_enum, help = "Format of the exported tasks")]
+    format: ExportFormat,
+    #[clap(long, help = "File to write the tasks to, defaults to stdout")]
+    output: Option<PathBuf>,
+}
 
-        return format!("- [{}] **{}**: {}", completed, self.id, self.task);
-    }
+#[derive(Clone, Debug, ValueEnum)]
+enum ExportFormat {
+    /// Plain text file in the todo.txt format
+    Todotxt,
+    /// Taskwarrior JSON that `task import` can read
+    Json,
 }
 
-impl TryFrom<(String, TaskStatus)> for Task {
-    type Error = anyhow::Error;
-    fn try_from(
-        (task, task_status): (String, TaskStatus),
-    ) -> std::result::Result<Self, Self::Error> {
-        let completed: bool = if task.starts_with("- [ ] **") || task.starts_with("- [x] **") {
-            task.chars().nth(3).unwrap() == 'x'
-        } else {
-            return Err(anyhow!("Error: Start of String {:?} is not valid", task));
-        };
-        if !completed {
-            if let TaskStatus::Complete = task_status {
-                return Err(anyhow!("Error: non complete task cannot be complete"));
-            }
-        }
-        let id: usize = get_id(&task)?;
-        let task: String = task
-            .chars()
-            .into_iter()
-            .skip_while(|e| e != &':')
-            .skip(2)
-            .collect();
-        return Ok(Task {
-            id,
-            task,
-            task_status,
-        });
-    }
+#[derive(Debug, Deserialize)]
+struct WarriorTask {
+    description: String,
+    #[serde(default)]
+    status: String,
+    priority: Option<String>,
+    due: Option<String>,
+    start: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    project: Option<String>,
 }
 
-impl Display for Task {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}\t{}\t{}", self.task_status, self.id, self.task)
+#[derive(Clone, Debug, Default)]
+struct ListMap(Vec<(String, TaskStatus)>);
+
+impl ListMap {
+    fn section(&self, list: &str, completed: bool) -> TaskStatus {
+        if completed {
+            return TaskStatus::Complete;
+        }
+        self.0
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(list.trim()))
+            .map_or(TaskStatus::Incomplete, |(_, section)| *section)
     }
 }
 
-fn get_id(task: &String) -> Result<usize, ParseIntError> {
-    return task
-        .chars()
-        .into_iter()
-        .skip(8)
-        .take_while(|c| c != &'*')
-        .collect::<String>()
-        .parse::<usize>();
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TodoList {
+    display_name: String,
+    #[serde(default)]
+    tasks: Vec<TodoTask>,
 }
 
-fn get_lines(path: &PathBuf) -> Result<Vec<String>> {
-    return Ok(BufReader::new(File::open(path)?)
-        .lines()
-        .collect::<Result<_, _>>()?);
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TodoTask {
+    title: String,
+    #[serde(default)]
+    status: String,
+    #[serde(default)]
+    importance: String,
+    due_date_time: Option<TodoDate>,
 }
 
-fn get_tasks_in_sections(lines: Vec<String>, sections: Vec<TaskStatus>) -> Vec<Task> {
-    let mut status: Option<TaskStatus> = None;
-    lines
-        .into_iter()
-        .filter_map(|line| {
-            if let Ok(s) = TaskStatus::try_from(&line) {
-                if sections.contains(&s) {
-                    status = Some(s);
-                } else {
-                    status = None;
-                }
-                None
-            } else if let Some(s) = status.clone() {
-                match Task::try_from((line, s)) {
-                    Ok(t) => Some(t),
-                    Err(_) => None,
-                }
-            } else {
-                None
-            }
-        })
-        .collect()
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TodoDate {
+    date_time: String,
 }
 
-fn get_section_start(lines: &Vec<String>, section: TaskStatus) -> Result<usize> {
-    return Ok(lines
-        .iter()
-        .position(|value| value == &format!("### {}", section.to_string().to_uppercase()))
-        .with_context(|| format!("could not find `{:?}` section start", section))?);
+/// CSV columns named with `--map`. Without a mapping for status the
+/// `Status` column is used when the file has one.
+#[derive(Clone, Debug)]
+struct ColumnMap {
+    title: String,
+    status: Option<String>,
+    due: Option<String>,
+    priority: Option<String>,
 }
 
-fn get_section_end(lines: &Vec<String>, section_start: usize) -> Result<usize> {
-    return Ok(section_start
-        + lines[section_start..lines.len()]
-            .iter()
-            .position(|x| x == "---")
-            .with_context(|| {
-                format!("could not find `{:?}` section end", lines[section_start])
-            })?);
+impl Default for ColumnMap {
+    fn default() -> Self {
+        ColumnMap {
+            title: String::from("Title"),
+            status: None,
+            due: None,
+            priority: None,
+        }
+    }
 }
 
-fn get_section_indexes(lines: &Vec<String>, section: TaskStatus) -> Result<(usize, usize)> {
-    let start = get_section_start(lines, section)?;
-    return Ok((start, get_section_end(lines, start)?));
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Config {
+    /// Task file used when no other file is given, relative to the config
+    /// file.
+    file: Option<PathBuf>,
+    color: Option<ColorChoice>,
+    read_only: bool,
+    allow_similar: bool,
+    strict_tags: bool,
+    backup: bool,
+    journal: bool,
+    history: Option<usize>,
+    list: ListOptions,
+    default_view: Option<String>,
+    #[serde(deserialize_with = "deserialize_views")]
+    views: BTreeMap<String, ListOptions>,
+    warnings: SectionLimits,
+    lint: LintRules,
+    ids: IdStyle,
+    dates: DateSettings,
+    capture: CaptureSettings,
+    /// Name recorded on added and checked tasks, defaults to `$USER`.
+    author: Option<String>,
 }
 
-fn move_task_to_section(
-    id: usize,
-    path: PathBuf,
-    section: TaskStatus,
-    allowed_sections: Vec<TaskStatus>,
-) -> Result<()> {
-    let lines: Vec<String> =
-        get_lines(&path).with_context(|| format!("could not read lines from file `{:?}`", path))?;
-    let mut tasks = get_tasks_in_sections(lines, TaskStatus::all());
-    for task in tasks.iter_mut() {
-        if task.id == id {
-            if allowed_sections.contains(&task.task_status) {
-                bail!("cannot move task from section `{:?}`", task.task_status);
-            }
-            task.task_status = section;
-            write_tasks_to_file(path, tasks)?;
-            return Ok(());
+impl Config {
+    fn author(&self) -> Result<Option<String>> {
+        let author = self
+            .author
+            .clone()
+            .or_else(|| env::var("USER").ok())
+            .filter(|author| !author.is_empty());
+        if let Some(author) = author.as_ref().filter(|a| a.contains(char::is_whitespace)) {
+            bail!("author `{}` cannot contain whitespace", author);
         }
+        Ok(author)
     }
-    bail!("could not find task with id `{:?}`", id);
 }
 
-fn write_tasks_to_file(path: PathBuf, tasks: Vec<Task>) -> Result<()> {
-    let mut lines: Vec<String> = vec![];
-    for (i, s) in TaskStatus::all().into_iter().enumerate() {
-        lines = add_section(lines, &tasks, s);
-        if i < TaskStatus::all().len() - 1 {
-            lines.push(String::from(""));
+/// The `[dates]` config table, see `DateStyle::new`.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct DateSettings {
+    format: String,
+    locale: String,
+    week_start: String,
+}
+
+impl Default for DateSettings {
+    fn default() -> Self {
+        DateSettings {
+            format: String::from(DATE_FORMAT),
+            locale: String::from("POSIX"),
+            week_start: String::from("monday"),
         }
     }
-    let mut file = OpenOptions::new().write(true).open(path)?;
-    file.set_len(lines.len() as u64)?;
-    file.seek(SeekFrom::Start(0))?;
-    for line in lines {
-        writeln!(file, "{}", line)?;
+}
+
+impl DateSettings {
+    fn style(&self) -> Result<DateStyle> {
+        DateStyle::new(&self.format, &self.locale, &self.week_start)
+            .context("invalid `[dates]` config")
     }
-    return Ok(());
 }
 
-fn add_section(mut lines: Vec<String>, tasks: &Vec<Task>, section: TaskStatus) -> Vec<String> {
-    lines.push(format!("### {}", section.to_string().to_uppercase()));
-    lines.push(String::from(""));
-    let filtered_tasks = tasks
-        .iter()
-        .filter(|e| e.task_status == section)
-        .collect::<Vec<&Task>>();
-    if filtered_tasks.len() != 0 {
-        for t in filtered_tasks {
-            lines.push(t.to_markdown());
+/// The `[capture]` config table with the sigils quick capture reads, see
+/// `CaptureGrammar`.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CaptureSettings {
+    tag: String,
+    priority: String,
+    date: String,
+}
+
+impl Default for CaptureSettings {
+    fn default() -> Self {
+        CaptureSettings {
+            tag: String::from("#"),
+            priority: String::from("!"),
+            date: String::new(),
         }
-        lines.push(String::from(""));
     }
-    lines.push(String::from("---"));
-    return lines;
 }
 
-fn get_task_id(task: &String) -> Result<usize, ParseIntError> {
-    return task
-        .chars()
-        .into_iter()
-        .skip(8)
-        .take_while(|e| e != &'*')
-        .collect::<String>()
-        .parse::<usize>();
+impl CaptureSettings {
+    fn grammar(&self) -> Result<CaptureGrammar> {
+        CaptureGrammar::new(&self.tag, &self.priority, &self.date)
+            .context("invalid `[capture]` config")
+    }
 }
 
-fn get_next_id(lines: &Vec<String>) -> usize {
-    return match lines.iter().filter_map(|e| get_task_id(e).ok()).max() {
-        Some(i) => i + 1,
-        None => 0,
-    };
+/// How task ids are shown, e.g. `PROJ-012` for prefix `PROJ-` and
+/// padding 3. The prefix must end in `-` so `parse_id` can read the ids
+/// back.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct IdStyle {
+    #[serde(deserialize_with = "deserialize_id_prefix")]
+    prefix: String,
+    padding: usize,
 }
 
-fn main() -> Result<()> {
-    let args = Cli::parse();
-    let quiet = args.quiet;
-    let path = match args.file {
-        Some(p) => p,
-        None => PathBuf::from(DEFAULT_TASK_FILE),
-    };
-    match args.command {
-        Commands::Add(options) => {
-            let task = options.task;
-            let section = if options.complete {
-                TaskStatus::Complete
-            } else if options.select {
-                TaskStatus::Selected
-            } else {
-                TaskStatus::Incomplete
-            };
-            let completed = if section == TaskStatus::Complete {
-                'x'
-            } else {
-                ' '
-            };
-            let mut lines: Vec<String> = get_lines(&path)
-                .with_context(|| format!("could not read lines from file `{:?}`", path))?;
-            let (section_start, section_end) = get_section_indexes(&lines, section)?;
+impl IdStyle {
+    fn format(&self, id: usize) -> String {
+        format!("{}{:0width$}", self.prefix, id, width = self.padding)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct LintRules {
+    disable: Vec<String>,
+    max_length: usize,
+    max_selected: Option<usize>,
+    forbidden_words: Vec<String>,
+}
+
+impl Default for LintRules {
+    fn default() -> Self {
+        LintRules {
+            disable: vec![],
+            max_length: 120,
+            max_selected: None,
+            forbidden_words: vec![],
+        }
+    }
+}
+
+impl LintRules {
+    fn enabled(&self, rule: &str) -> bool {
+        !self.disable.iter().any(|r| r == rule)
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+struct Projects {
+    projects: BTreeMap<String, PathBuf>,
+}
+
+impl Projects {
+    fn file(&self, name: &str) -> Result<PathBuf> {
+        self.projects.get(name).cloned().with_context(|| {
+            format!(
+                "could not find project `{}`, add it with `markdone project add`",
+                name
+            )
+        })
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SectionLimits {
+    selected: Option<usize>,
+    incomplete: Option<usize>,
+    complete: Option<usize>,
+}
+
+impl SectionLimits {
+    fn is_empty(&self) -> bool {
+        self.selected.is_none() && self.incomplete.is_none() && self.complete.is_none()
+    }
+
+    fn get(&self, section: &TaskStatus) -> Option<usize> {
+        match section {
+            TaskStatus::Selected => self.selected,
+            TaskStatus::Incomplete => self.incomplete,
+            TaskStatus::Complete => self.complete,
+        }
+    }
+}
+
+fn supports_color() -> bool {
+    stdout().is_terminal()
+        && env::var_os("NO_COLOR").is_none()
+        && env::var("TERM").map_or(true, |term| term != "dumb")
+}
+
+fn find_url(text: &str) -> Option<String> {
+    text.split_whitespace()
+        .map(|word| word.trim_matches(|c| c == '<' || c == '>' || c == '(' || c == ')'))
+        .find(|word| word.starts_with("http://") || word.starts_with("https://"))
+        .map(String::from)
+}
+
+fn open_url(url: &str) -> Result<()> {
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = process::Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else if cfg!(target_os = "macos") {
+        process::Command::new("open")
+    } else {
+        process::Command::new("xdg-open")
+    };
+    let status = command
+        .arg(url)
+        .status()
+        .with_context(|| format!("could not open url `{}`", url))?;
+    if !status.success() {
+        bail!("could not open url `{}`", url);
+    }
+    Ok(())
+}
+
+fn edit_in_editor(text: &str) -> Result<String> {
+    let contents = edit_file_in_editor(text)?;
+    match contents.lines().map(str::trim).find(|l| !l.is_empty()) {
+        Some(line) => Ok(line.to_string()),
+        None => bail!("task text cannot be empty"),
+    }
+}
+
+fn edit_file_in_editor(text: &str) -> Result<String> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| String::from("vi"));
+    let mut args = editor.split_whitespace();
+    let program = args.next().context("editor command is empty")?;
+    let file = env::temp_dir().join(format!("markdone-{}.md", process::id()));
+    fs::write(&file, format!("{}\n", text))
+        .with_context(|| format!("could not write to file `{:?}`", file))?;
+    let status = process::Command::new(program)
+        .args(args)
+        .arg(&file)
+        .status()
+        .with_context(|| format!("could not run editor `{}`", editor));
+    let contents = fs::read_to_string(&file);
+    fs::remove_file(&file).ok();
+    if !status?.success() {
+        bail!("editor `{}` exited with an error", editor);
+    }
+    contents.with_context(|| format!("could not read file `{:?}`", file))
+}
+
+fn show_task(task: &Task, ids: &IdStyle, dates: &DateStyle) -> Vec<String> {
+    let mut lines = vec![
+        format!("id: {}", ids.format(task.id)),
+        format!("status: {}", task.task_status),
+        format!("text: {}", task.task),
+    ];
+    if !task.tags.is_empty() {
+        lines.push(format!("tags: {}", task.tags.join(", ")));
+    }
+    if let Some(priority) = task.priority {
+        lines.push(format!("priority: {}", priority));
+    }
+    if let Some(target) = task.target {
+        let missed = if task.missed_target() {
+            " (missed)"
+        } else {
+            ""
+        };
+        lines.push(format!("target: {}{}", dates.format(target), missed));
+    }
+    if let Some(due) = task.due {
+        let overdue = if task.is_overdue() { " (overdue)" } else { "" };
+        lines.push(format!("due: {}{}", dates.format(due), overdue));
+    }
+    if let Some(repeat) = task.repeat {
+        lines.push(format!("repeats: {}", repeat));
+    }
+    if let Some(template) = &task.template {
+        lines.push(format!("template: {}", template));
+    }
+    if let Some(created) = task.created {
+        lines.push(format!("added: {}", dates.format(created)));
+    }
+    if let Some(selected) = task.selected {
+        lines.push(format!("selected: {}", dates.format(selected)));
+    }
+    if let Some(completed) = task.completed {
+        lines.push(format!("completed: {}", dates.format(completed)));
+    }
+    if let Some(updated) = task.updated {
+        lines.push(format!("changed: {}", dates.format(updated)));
+    }
+    if let Some(added_by) = &task.added_by {
+        lines.push(format!("added by: {}", added_by));
+    }
+    if let Some(checked_by) = &task.checked_by {
+        lines.push(format!("checked by: {}", checked_by));
+    }
+    if let Some(url) = &task.url {
+        lines.push(format!("link: {}", url));
+    }
+    let flags: Vec<&str> = [
+        (task.carried, "carried over"),
+        (task.starred, "starred"),
+        (task.pinned, "pinned"),
+        (task.protected, "protected"),
+    ]
+    .into_iter()
+    .filter_map(|(set, flag)| set.then_some(flag))
+    .collect();
+    if !flags.is_empty() {
+        lines.push(format!("flags: {}", flags.join(", ")));
+    }
+    if !task.notes.is_empty() {
+        lines.push(String::from("notes:"));
+        lines.extend(task.notes.iter().map(|note| format!("  {}", note)));
+    }
+    lines
+}
+
+/// Items of a markdown list in `contents` with whether their checkbox is
+/// checked. Without any list items every non-empty line is an item.
+fn parse_list_items(contents: &str) -> Vec<(String, bool)> {
+    let item = Regex::new(r"^\s*(?:[-*+]|\d+[.)])\s+(?:\[([ xX])\]\s+)?(.*\S)\s*$")
+        .expect("list item pattern is valid");
+    let items: Vec<(String, bool)> = contents
+        .lines()
+        .filter_map(|line| item.captures(line))
+        .map(|c| {
+            let checked = c.get(1).is_some_and(|m| m.as_str() != " ");
+            (c[2].to_string(), checked)
+        })
+        .collect();
+    if !items.is_empty() {
+        return items;
+    }
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| (line.to_string(), false))
+        .collect()
+}
+
+/// Ids referenced in `text` as `#12`, or with the configured id prefix
+/// as e.g. `PROJ-012`, in order of first appearance.
+fn find_references(text: &str, ids: &IdStyle) -> Vec<usize> {
+    let mut pattern = String::from(r"(?:^|[^\w&])#(\d+)\b");
+    if !ids.prefix.is_empty() {
+        pattern.push_str(&format!(r"|\b{}(\d+)\b", regex::escape(&ids.prefix)));
+    }
+    let pattern = Regex::new(&pattern).expect("escaped prefix is a valid regex");
+    let mut found = vec![];
+    for captures in pattern.captures_iter(text) {
+        let id = captures
+            .get(1)
+            .or_else(|| captures.get(2))
+            .and_then(|m| m.as_str().parse().ok());
+        if let Some(id) = id.filter(|id| !found.contains(id)) {
+            found.push(id);
+        }
+    }
+    found
+}
+
+/// Line number of the task with `id` in the task file `lines`, from 1.
+fn task_line(lines: &[String], id: usize) -> Option<usize> {
+    let open = format!("- [ ] **{}**:", id);
+    let done = format!("- [x] **{}**:", id);
+    lines
+        .iter()
+        .position(|line| line.starts_with(&open) || line.starts_with(&done))
+        .map(|index| index + 1)
+}
+
+fn parse_delimited(contents: &str, delimiter: char) -> Result<Vec<Vec<String>>> {
+    let mut rows: Vec<Vec<String>> = vec![];
+    let mut row: Vec<String> = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = contents.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            if c == '"' {
+                if chars.peek() == Some(&'"') {
+                    field.push('"');
+                    chars.next();
+                } else {
+                    quoted = false;
+                }
+            } else {
+                field.push(c);
+            }
+        } else if c == '"' {
+            quoted = true;
+        } else if c == delimiter {
+            row.push(std::mem::take(&mut field));
+        } else if c == '\n' || c == '\r' {
+            if c == '\r' && chars.peek() == Some(&'\n') {
+                chars.next();
+            }
+            row.push(std::mem::take(&mut field));
+            rows.push(std::mem::take(&mut row));
+        } else {
+            field.push(c);
+        }
+    }
+    if quoted {
+        bail!("unterminated quoted field");
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows.retain(|r| r.iter().any(|f| !f.is_empty()));
+    Ok(rows)
+}
+
+fn column_index(header: &[String], name: &str) -> Option<usize> {
+    header
+        .iter()
+        .position(|h| h.trim().eq_ignore_ascii_case(name))
+}
+
+fn parse_column_map(map: &str) -> Result<ColumnMap> {
+    let mut title = None;
+    let mut status = None;
+    let mut due = None;
+    let mut priority = None;
+    for pair in map.split(',') {
+        let (field, column) = pair
+            .split_once('=')
+            .with_context(|| format!("expected `field=column`, found `{}`", pair))?;
+        match field.trim() {
+            "title" => title = Some(column.trim().to_string()),
+            "status" => status = Some(column.trim().to_string()),
+            "due" => due = Some(column.trim().to_string()),
+            "priority" => priority = Some(column.trim().to_string()),
+            f => bail!("cannot map unknown field `{}`", f),
+        }
+    }
+    Ok(ColumnMap {
+        title: title.context("missing mapping for `title`")?,
+        status,
+        due,
+        priority,
+    })
+}
+
+fn status_from_column(value: &str) -> TaskStatus {
+    match value.trim().to_lowercase().as_str() {
+        "complete" | "completed" | "done" | "closed" => TaskStatus::Complete,
+        "selected" | "in progress" | "doing" => TaskStatus::Selected,
+        _ => TaskStatus::Incomplete,
+    }
+}
+
+fn field(row: &[String], i: Option<usize>) -> String {
+    i.and_then(|i| row.get(i))
+        .map(|f| f.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// Read a priority the way CSV exports write it, including ClickUp's
+/// urgent and normal.
+fn priority_from_column(value: &str) -> Result<Priority> {
+    match value.to_lowercase().as_str() {
+        "urgent" => Ok(Priority::High),
+        "normal" => Ok(Priority::Medium),
+        _ => parse_priority(value),
+    }
+}
+
+fn import_csv(
+    contents: &str,
+    delimiter: char,
+    map: &ColumnMap,
+    warnings: bool,
+) -> Result<Vec<Task>> {
+    let rows = parse_delimited(contents, delimiter)?;
+    let (header, rows) = rows.split_first().context("import file is empty")?;
+    let column = |name: &str| -> Result<usize> {
+        column_index(header, name).with_context(|| format!("could not find `{}` column", name))
+    };
+    let title = column(&map.title)?;
+    let status = match map.status.as_deref() {
+        Some(name) => Some(column(name)?),
+        None => column_index(header, "Status"),
+    };
+    let due = map.due.as_deref().map(column).transpose()?;
+    let priority = map.priority.as_deref().map(column).transpose()?;
+    let mut imported = vec![];
+    for row in rows {
+        let text = field(row, Some(title));
+        if text.is_empty() {
+            continue;
+        }
+        let mut task = Task::new(0, text, status_from_column(&field(row, status)));
+        let date = field(row, due);
+        if !date.is_empty() {
+            task.due = Some(parse_date(&date)?);
+        }
+        let value = field(row, priority);
+        if !value.is_empty() {
+            match priority_from_column(&value) {
+                Ok(priority) => task.priority = Some(priority),
+                Err(error) if warnings => {
+                    eprintln!("warning: skipped priority of `{}`: {:#}", task.task, error)
+                }
+                Err(_) => {}
+            }
+        }
+        imported.push(task);
+    }
+    Ok(imported)
+}
+
+fn parse_list_map(map: &str) -> Result<ListMap> {
+    let mut lists = vec![];
+    for pair in map.split(',') {
+        let (list, section) = pair
+            .split_once('=')
+            .with_context(|| format!("expected `list=section`, found `{}`", pair))?;
+        let section = TaskStatus::from_str(section.trim(), true)
+            .map_err(|_| anyhow!("invalid section `{}`", section.trim()))?;
+        lists.push((list.trim().to_string(), section));
+    }
+    Ok(ListMap(lists))
+}
+
+fn import_apple_reminders(contents: &str, delimiter: char, lists: &ListMap) -> Result<Vec<Task>> {
+    let rows = parse_delimited(contents, delimiter)?;
+    let (header, rows) = rows.split_first().context("import file is empty")?;
+    let title = column_index(header, "Title").context("could not find `Title` column")?;
+    let list = column_index(header, "List");
+    let completed = column_index(header, "Completed");
+    let due = column_index(header, "Due Date");
+    let priority = column_index(header, "Priority");
+    let mut imported = vec![];
+    for row in rows {
+        let text = field(row, Some(title));
+        if text.is_empty() {
+            continue;
+        }
+        let done = matches!(
+            field(row, completed).to_lowercase().as_str(),
+            "yes" | "true" | "1"
+        );
+        let mut task = Task::new(0, text, lists.section(&field(row, list), done));
+        task.due = field(row, due)
+            .get(..10)
+            .and_then(|d| NaiveDate::parse_from_str(d, DATE_FORMAT).ok());
+        task.priority = match field(row, priority).to_lowercase().as_str() {
+            "high" | "1" => Some(Priority::High),
+            "medium" | "5" => Some(Priority::Medium),
+            "low" | "9" => Some(Priority::Low),
+            _ => None,
+        };
+        imported.push(task);
+    }
+    Ok(imported)
+}
+
+fn import_microsoft_todo(contents: &str, lists: &ListMap) -> Result<Vec<Task>> {
+    let mut value: serde_json::Value = serde_json::from_str(contents)?;
+    if let Some(inner) = value.get_mut("value") {
+        value = inner.take();
+    }
+    let todo_lists: Vec<TodoList> = match serde_json::from_value::<Vec<TodoList>>(value.clone()) {
+        Ok(todo_lists) => todo_lists,
+        Err(_) => vec![TodoList {
+            display_name: String::new(),
+            tasks: serde_json::from_value(value)
+                .context("expected a list of To Do lists or tasks")?,
+        }],
+    };
+    let mut imported = vec![];
+    for list in todo_lists {
+        for todo in list.tasks {
+            if todo.title.trim().is_empty() {
+                continue;
+            }
+            let section = lists.section(&list.display_name, todo.status == "completed");
+            let mut task = Task::new(0, todo.title.trim().to_string(), section);
+            task.due = todo.due_date_time.and_then(|d| {
+                d.date_time
+                    .get(..10)
+                    .and_then(|d| NaiveDate::parse_from_str(d, DATE_FORMAT).ok())
+            });
+            task.priority = match todo.importance.as_str() {
+                "high" => Some(Priority::High),
+                "low" => Some(Priority::Low),
+                _ => None,
+            };
+            imported.push(task);
+        }
+    }
+    Ok(imported)
+}
+
+fn todotxt_priority(priority: &str) -> Option<Priority> {
+    let letter = priority.strip_prefix('(')?.strip_suffix(')')?;
+    match letter {
+        "A" => Some(Priority::High),
+        "B" => Some(Priority::Medium),
+        l if l.len() == 1 && l.chars().all(|c| c.is_ascii_uppercase()) => Some(Priority::Low),
+        _ => None,
+    }
+}
+
+fn import_todotxt(contents: &str) -> Result<Vec<Task>> {
+    let mut imported = vec![];
+    for line in contents.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let mut words = line.split_whitespace().peekable();
+        let done = words.next_if_eq(&"x").is_some();
+        let mut priority = words
+            .next_if(|w| todotxt_priority(w).is_some())
+            .and_then(todotxt_priority);
+        // Completion and creation dates have nowhere to go in markdone.
+        while words
+            .next_if(|w| NaiveDate::parse_from_str(w, DATE_FORMAT).is_ok())
+            .is_some()
+        {}
+        let task_status = if done {
+            TaskStatus::Complete
+        } else {
+            TaskStatus::Incomplete
+        };
+        let mut task = Task::new(0, String::new(), task_status);
+        let mut text = vec![];
+        for word in words {
+            if let Some(tag) = word.strip_prefix(['+', '@']) {
+                if let Ok(tag) = parse_tag(tag) {
+                    task.tags.push(tag);
+                    continue;
+                }
+            }
+            match word.split_once(':') {
+                Some(("due", value)) => {
+                    if let Ok(due) = NaiveDate::parse_from_str(value, DATE_FORMAT) {
+                        task.due = Some(due);
+                        continue;
+                    }
+                }
+                Some(("rec", value)) => {
+                    if let Ok(repeat) = parse_repeat(value.trim_start_matches('+')) {
+                        task.repeat = Some(repeat);
+                        continue;
+                    }
+                }
+                // Clients keep the priority of completed tasks here.
+                Some(("pri", value)) => {
+                    if let Some(p) = todotxt_priority(&format!("({})", value)) {
+                        priority = priority.or(Some(p));
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+            text.push(word);
+        }
+        if text.is_empty() {
+            continue;
+        }
+        task.task = text.join(" ");
+        task.priority = priority;
+        imported.push(task);
+    }
+    Ok(imported)
+}
+
+fn import_taskwarrior(contents: &str) -> Result<Vec<Task>> {
+    let warrior: Vec<WarriorTask> = serde_json::from_str(contents)
+        .context("expected a list of tasks as written by `task export`")?;
+    let mut imported = vec![];
+    for warrior in warrior {
+        // Deleted tasks are gone and recurring ones are only templates for
+        // the pending tasks Taskwarrior generates from them.
+        let task_status = match warrior.status.as_str() {
+            "deleted" | "recurring" => continue,
+            "completed" => TaskStatus::Complete,
+            _ if warrior.start.is_some() => TaskStatus::Selected,
+            _ => TaskStatus::Incomplete,
+        };
+        if warrior.description.trim().is_empty() {
+            continue;
+        }
+        let mut task = Task::new(0, warrior.description.trim().to_string(), task_status);
+        task.priority = match warrior.priority.as_deref() {
+            Some("H") => Some(Priority::High),
+            Some("M") => Some(Priority::Medium),
+            Some("L") => Some(Priority::Low),
+            _ => None,
+        };
+        task.due = warrior
+            .due
+            .as_deref()
+            .and_then(|d| NaiveDate::parse_from_str(d.get(..8)?, "%Y%m%d").ok());
+        let project = warrior.project.map(|p| p.replace('.', "/"));
+        task.tags = project
+            .into_iter()
+            .chain(warrior.tags)
+            .filter_map(|tag| parse_tag(&tag).ok())
+            .collect();
+        imported.push(task);
+    }
+    Ok(imported)
+}
+
+fn export_todotxt(task: &Task) -> String {
+    let letter = task.priority.map(|priority| match priority {
+        Priority::High => 'A',
+        Priority::Medium => 'B',
+        Priority::Low => 'C',
+    });
+    let mut words = vec![];
+    match (task.task_status, letter) {
+        (TaskStatus::Complete, _) => words.push(String::from("x")),
+        (_, Some(letter)) => words.push(format!("({})", letter)),
+        _ => {}
+    }
+    words.push(task.task.clone());
+    words.extend(task.url.clone());
+    words.extend(task.tags.iter().map(|tag| format!("+{}", tag)));
+    if let Some(due) = task.due {
+        words.push(format!("due:{}", due.format(DATE_FORMAT)));
+    }
+    if let Some(repeat) = task.repeat {
+        let unit = match repeat.unit {
+            RepeatUnit::Day => 'd',
+            RepeatUnit::Week => 'w',
+            RepeatUnit::Month => 'm',
+            RepeatUnit::Year => 'y',
+        };
+        words.push(format!("rec:{}{}", repeat.every, unit));
+    }
+    if let (TaskStatus::Complete, Some(letter)) = (task.task_status, letter) {
+        words.push(format!("pri:{}", letter));
+    }
+    words.join(" ")
+}
+
+fn export_taskwarrior(tasks: &TaskList) -> Result<String> {
+    // Taskwarrior has no section for selected tasks, so they are exported
+    // as started now, which is what marks a task active there.
+    let now = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let warrior: Vec<serde_json::Value> = tasks
+        .iter()
+        .map(|task| {
+            let mut warrior = serde_json::json!({
+                "description": match &task.url {
+                    Some(url) => format!("{} {}", task.task, url),
+                    None => task.task.clone(),
+                },
+                "status": match task.task_status {
+                    TaskStatus::Complete => "completed",
+                    _ => "pending",
+                },
+            });
+            if task.task_status == TaskStatus::Selected {
+                warrior["start"] = now.clone().into();
+            }
+            if let Some(priority) = task.priority {
+                warrior["priority"] = match priority {
+                    Priority::High => "H",
+                    Priority::Medium => "M",
+                    Priority::Low => "L",
+                }
+                .into();
+            }
+            if let Some(due) = task.due {
+                warrior["due"] = due.format("%Y%m%dT000000Z").to_string().into();
+            }
+            if !task.tags.is_empty() {
+                warrior["tags"] = task.tags.clone().into();
+            }
+            warrior
+        })
+        .collect();
+    Ok(serde_json::to_string_pretty(&warrior)?)
+}
+
+fn import_github_projects(contents: &str, delimiter: char, warnings: bool) -> Result<Vec<Task>> {
+    let rows = parse_delimited(contents, delimiter)?;
+    let (header, rows) = rows.split_first().context("import file is empty")?;
+    let title = column_index(header, "Title").context("could not find `Title` column")?;
+    let status = column_index(header, "Status");
+    let url = column_index(header, "URL");
+    let assignees = column_index(header, "Assignees");
+    let mut imported = vec![];
+    for row in rows {
+        let text = field(row, Some(title));
+        if text.is_empty() {
+            continue;
+        }
+        let mut task = Task::new(0, text, status_from_column(&field(row, status)));
+        // Assignees become tags, logins that are not valid tags such as
+        // all-digit ones are dropped.
+        for assignee in field(row, assignees).split(',').map(str::trim) {
+            match parse_tag(assignee) {
+                Ok(tag) => task.tags.push(tag),
+                Err(_) if warnings && !assignee.is_empty() => eprintln!(
+                    "warning: dropped assignee `{}` of `{}`, it is not a valid tag",
+                    assignee, task.task
+                ),
+                Err(_) => {}
+            }
+        }
+        task.url = Some(field(row, url)).filter(|url| !url.is_empty());
+        imported.push(task);
+    }
+    Ok(imported)
+}
+
+fn supports_hyperlinks() -> bool {
+    stdout().is_terminal() && env::var("TERM").map_or(true, |term| term != "dumb")
+}
+
+fn write_delimited(tasks: &[(Option<&str>, &Task)], delimiter: char) -> Result<()> {
+    let escape = |field: String| -> String {
+        if delimiter == '\t' {
+            field.replace(['\t', '\n', '\r'], " ")
+        } else if field.contains([delimiter, '"', '\n', '\r']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field
+        }
+    };
+    let projects = tasks.iter().any(|(project, _)| project.is_some());
+    let stdout = stdout();
+    let mut handle = stdout.lock();
+    let mut header = vec!["id", "status", "text", "tags", "priority", "due", "url"];
+    if projects {
+        header.insert(0, "project");
+    }
+    writeln!(handle, "{}", header.join(&delimiter.to_string()))?;
+    for (project, t) in tasks {
+        let mut fields = vec![
+            t.id.to_string(),
+            t.task_status.to_string(),
+            t.task.clone(),
+            t.tags.join(" "),
+            t.priority.map(|p| p.to_string()).unwrap_or_default(),
+            t.due
+                .map(|d| d.format(DATE_FORMAT).to_string())
+                .unwrap_or_default(),
+            t.url.clone().unwrap_or_default(),
+        ];
+        if projects {
+            fields.insert(0, project.unwrap_or_default().to_string());
+        }
+        let fields: Vec<String> = fields.into_iter().map(escape).collect();
+        writeln!(handle, "{}", fields.join(&delimiter.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Fall back to the default view or the `[list]` config when `options`
+/// has no filters of its own.
+fn resolve_list_options(options: ListOptions, config: &mut Config) -> Result<ListOptions> {
+    let count_only = options.count_only;
+    let (archived, include_archive, all_projects) = (
+        options.archived,
+        options.include_archive,
+        options.all_projects,
+    );
+    let format = options.format;
+    let format_string = options.format_string.clone();
+    let legend = options.legend || config.list.legend;
+    let mut options = if options.has_filters() {
+        options
+    } else if let Some(name) = &config.default_view {
+        config
+            .views
+            .remove(name)
+            .with_context(|| format!("could not find default view `{}`", name))?
+    } else {
+        mem::take(&mut config.list)
+    };
+    options.count_only = count_only;
+    options.archived |= archived;
+    options.include_archive |= include_archive;
+    options.all_projects |= all_projects;
+    options.legend |= legend;
+    if format.is_some() {
+        options.format = format;
+    }
+    if format_string.is_some() {
+        options.format_string = format_string;
+    }
+    Ok(options)
+}
+
+fn list_sources(options: &ListOptions, path: &Path) -> Result<Vec<(Option<String>, PathBuf)>> {
+    let files: Vec<(Option<String>, PathBuf)> = if options.all_projects {
+        load_projects()?
+            .projects
+            .into_iter()
+            .map(|(name, file)| (Some(name), file))
+            .collect()
+    } else {
+        vec![(None, path.to_path_buf())]
+    };
+    let mut sources = vec![];
+    for (name, file) in files {
+        let archive = archive_path(&file);
+        if options.archived {
+            sources.push((name, archive));
+            continue;
+        }
+        sources.push((name.clone(), file));
+        if options.include_archive && archive.exists() {
+            sources.push((name, archive));
+        }
+    }
+    Ok(sources)
+}
+
+/// A task as `list --format json` prints it, see `list_schema`.
+fn task_json(task: &Task, project: Option<&str>, file: Option<&str>) -> Result<serde_json::Value> {
+    let mut value = serde_json::to_value(task)?;
+    if let Some(object) = value.as_object_mut() {
+        if let Some(project) = project {
+            object.insert(String::from("project"), project.into());
+        }
+        if let Some(file) = file {
+            object.insert(String::from("file"), file.into());
+        }
+    }
+    Ok(value)
+}
+
+/// JSON Schema of the array printed by `list --format json`.
+fn list_schema() -> serde_json::Value {
+    let date = serde_json::json!({ "type": ["string", "null"], "format": "date" });
+    let optional = serde_json::json!({ "type": ["string", "null"] });
+    let flag = serde_json::json!({ "type": "boolean" });
+    let strings = serde_json::json!({ "type": "array", "items": { "type": "string" } });
+    let statuses: Vec<String> = TaskStatus::all().iter().map(|s| s.to_string()).collect();
+    let mut priorities: Vec<serde_json::Value> = [Priority::High, Priority::Medium, Priority::Low]
+        .iter()
+        .map(|p| p.to_string().into())
+        .collect();
+    priorities.push(serde_json::Value::Null);
+    serde_json::json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "markdone tasks",
+        "type": "array",
+        "items": {
+            "type": "object",
+            "properties": {
+                "id": { "type": "integer", "minimum": 0 },
+                "text": { "type": "string" },
+                "status": { "enum": statuses },
+                "protected": flag,
+                "pinned": flag,
+                "starred": flag,
+                "carried": flag,
+                "url": optional,
+                "target": date,
+                "due": date,
+                "tags": strings,
+                "priority": { "enum": priorities },
+                "repeat": optional,
+                "created": date,
+                "selected": date,
+                "completed": date,
+                "updated": date,
+                "added_by": optional,
+                "checked_by": optional,
+                "notes": strings,
+                "template": optional,
+                "project": {
+                    "type": "string",
+                    "description": "Registered project the task is from, with `--all-projects`"
+                },
+                "file": {
+                    "type": "string",
+                    "description": "Task file the task is from, when listing several files"
+                }
+            },
+            "required": [
+                "id", "text", "status", "protected", "pinned", "starred", "carried", "url",
+                "target", "due", "tags", "priority", "repeat", "created", "selected",
+                "completed", "updated", "added_by", "checked_by", "notes", "template"
+            ],
+            "additionalProperties": false
+        }
+    })
+}
+
+fn list_tasks(
+    sources: &[(Option<String>, PathBuf)],
+    options: &ListOptions,
+    config: &Config,
+    quiet: bool,
+    hyperlinks: bool,
+    color: bool,
+    output: OutputMode,
+) -> Result<()> {
+    let ids = &config.ids;
+    let dates = config.dates.style()?;
+    let filter = options.filter();
+    let sections = filter.sections();
+    let mut tasks: Vec<(usize, Task)> = vec![];
+    for (i, (_, path)) in sources.iter().enumerate() {
+        tasks.extend(
+            TaskList::load_tasks(path, &sections)?
+                .into_iter()
+                .filter(|t| filter.matches(t))
+                .map(|t| (i, t)),
+        );
+    }
+    if options.count_only {
+        println!("{}", tasks.len());
+        return Ok(());
+    }
+    match options.sort {
+        Some(SortKey::Due) => tasks.sort_by_key(|(_, t)| (t.due.is_none(), t.due)),
+        Some(SortKey::Priority) => tasks.sort_by_key(|(_, t)| std::cmp::Reverse(t.priority_rank())),
+        Some(SortKey::Created) => tasks.sort_by_key(|(_, t)| (t.created.is_none(), t.created)),
+        Some(SortKey::Completed) => {
+            tasks.sort_by_key(|(_, t)| (t.completed.is_none(), t.completed))
+        }
+        None => {}
+    }
+    tasks.sort_by_key(|(_, t)| !t.pinned);
+    // Listing several files shows each file's tasks together.
+    let grouped = sources.len() > 1;
+    if grouped {
+        tasks.sort_by_key(|(i, _)| *i);
+    }
+    let files = relative_paths(sources);
+    let listed: Vec<(Option<&str>, &Task)> = tasks
+        .iter()
+        .map(|(i, t)| (sources[*i].0.as_deref(), t))
+        .collect();
+    match options.format {
+        Some(ListFormat::Json) => {
+            let mut values = vec![];
+            for (i, t) in tasks.iter() {
+                let file = Some(files[*i].as_str()).filter(|_| grouped);
+                values.push(task_json(t, sources[*i].0.as_deref(), file)?);
+            }
+            println!("{}", serde_json::to_string_pretty(&values)?);
+            return Ok(());
+        }
+        Some(ListFormat::Tsv) => return write_delimited(&listed, '\t'),
+        Some(ListFormat::Csv) => return write_delimited(&listed, ','),
+        Some(ListFormat::Table) | None => {}
+    }
+    let group_title = |i: usize| -> String {
+        let count = tasks.iter().filter(|(j, _)| *j == i).count();
+        let noun = if count == 1 { "task" } else { "tasks" };
+        match &sources[i].0 {
+            Some(project) => format!("{}: {}, {} {}", project, files[i], count, noun),
+            None => format!("{}, {} {}", files[i], count, noun),
+        }
+    };
+    if output == OutputMode::ScreenReader {
+        let stdout = stdout();
+        let mut handle = stdout.lock();
+        if !quiet {
+            writeln!(handle, "{} tasks", tasks.len())?;
+        }
+        let mut group = None;
+        for (i, t) in tasks.iter() {
+            if grouped && group != Some(*i) {
+                group = Some(*i);
+                writeln!(handle, "In {}", group_title(*i))?;
+            }
+            writeln!(handle, "{}", t.describe(&dates))?;
+        }
+        return Ok(());
+    }
+    if let Some(template) = &options.format_string {
+        let stdout = stdout();
+        let mut handle = stdout.lock();
+        for (project, t) in listed.iter() {
+            writeln!(
+                handle,
+                "{}",
+                render_template(template, *project, t, ids, &dates, hyperlinks, color)?
+            )?;
+        }
+        return Ok(());
+    }
+    if !quiet {
+        if options.legend {
+            for (_, path) in sources {
+                let file = fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+                println!("file: {}", file.display());
+            }
+            let mut legend: Vec<String> = MARKERS
+                .iter()
+                .map(|(marker, meaning)| format!("{} {}", marker, meaning))
+                .collect();
+            if color {
+                legend.push(String::from("\x1b[33myellow\x1b[0m target missed"));
+                legend.push(String::from("\x1b[31mred\x1b[0m overdue"));
+            }
+            println!("legend: {}\n", legend.join("  "));
+        }
+        if !grouped {
+            println!("status\t\tid\ttask\n------\t\t--\t----");
+        }
+    }
+    let file_urls: Vec<String> = sources
+        .iter()
+        .map(|(_, path)| match fs::canonicalize(path) {
+            Ok(p) => format!("file://{}", p.display()),
+            Err(_) => String::new(),
+        })
+        .collect();
+    let stdout = stdout();
+    let mut handle = stdout.lock();
+    let mut group = None;
+    for (i, t) in tasks.iter() {
+        if grouped && group != Some(*i) {
+            if group.is_some() {
+                writeln!(handle)?;
+            }
+            group = Some(*i);
+            writeln!(handle, "{}", group_title(*i))?;
+            if !quiet {
+                writeln!(handle, "status\t\tid\ttask\n------\t\t--\t----")?;
+            }
+        }
+        let id = if hyperlinks {
+            hyperlink(t.url.as_ref().unwrap_or(&file_urls[*i]), &ids.format(t.id))
+        } else {
+            ids.format(t.id)
+        };
+        writeln!(
+            handle,
+            "{}\t{}\t{}",
+            paint_status(t.task_status, color),
+            id,
+            paint_text(t, hyperlinks, color, &dates)
+        )?;
+    }
+    Ok(())
+}
+
+/// Paths of `sources` relative to the deepest directory they share.
+fn relative_paths(sources: &[(Option<String>, PathBuf)]) -> Vec<String> {
+    let paths: Vec<PathBuf> = sources
+        .iter()
+        .map(|(_, path)| fs::canonicalize(path).unwrap_or_else(|_| path.clone()))
+        .collect();
+    let mut root = match paths.first().and_then(|p| p.parent()) {
+        Some(root) => root.to_path_buf(),
+        None => return vec![],
+    };
+    while !paths.iter().all(|p| p.starts_with(&root)) {
+        if !root.pop() {
+            break;
+        }
+    }
+    paths
+        .iter()
+        .map(|p| p.strip_prefix(&root).unwrap_or(p).display().to_string())
+        .collect()
+}
+
+fn paint_status(status: TaskStatus, color: bool) -> String {
+    match (status, color) {
+        (TaskStatus::Selected, true) => format!("\x1b[33m{}\x1b[0m", status),
+        (TaskStatus::Complete, true) => format!("\x1b[32m{}\x1b[0m", status),
+        _ => status.to_string(),
+    }
+}
+
+fn paint_text(task: &Task, hyperlinks: bool, color: bool, dates: &DateStyle) -> String {
+    let text = task.render_text(hyperlinks, color, dates);
+    if color && task.task_status == TaskStatus::Complete {
+        format!("\x1b[2m{}\x1b[0m", text)
+    } else {
+        text
+    }
+}
+
+fn format_date(date: Option<NaiveDate>) -> String {
+    date.map(|d| d.format(DATE_FORMAT).to_string())
+        .unwrap_or_default()
+}
+
+fn render_template(
+    template: &str,
+    project: Option<&str>,
+    task: &Task,
+    ids: &IdStyle,
+    dates: &DateStyle,
+    hyperlinks: bool,
+    color: bool,
+) -> Result<String> {
+    let mut rendered = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('t') => rendered.push('\t'),
+                Some('n') => rendered.push('\n'),
+                Some(c) => {
+                    rendered.push('\\');
+                    rendered.push(c);
+                }
+                None => rendered.push('\\'),
+            },
+            '{' if chars.next_if_eq(&'{').is_some() => rendered.push('{'),
+            '}' if chars.next_if_eq(&'}').is_some() => rendered.push('}'),
+            '{' => {
+                let name: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                let value = match name.as_str() {
+                    "id" => ids.format(task.id),
+                    "status" => paint_status(task.task_status, color),
+                    "task" => paint_text(task, hyperlinks, color, dates),
+                    "text" => task.task.clone(),
+                    "tags" => task.tags.join(","),
+                    "priority" => task.priority.map(|p| p.to_string()).unwrap_or_default(),
+                    "due" => format_date(task.due),
+                    "target" => format_date(task.target),
+                    "repeat" => task.repeat.map(|r| r.to_string()).unwrap_or_default(),
+                    "created" => format_date(task.created),
+                    "selected" => format_date(task.selected),
+                    "completed" => format_date(task.completed),
+                    "updated" => format_date(task.updated),
+                    "added_by" => task.added_by.clone().unwrap_or_default(),
+                    "checked_by" => task.checked_by.clone().unwrap_or_default(),
+                    "url" => task.url.clone().unwrap_or_default(),
+                    "project" => project.unwrap_or_default().to_string(),
+                    _ => bail!(
+                        "unknown field `{{{}}}` in format string, expected one of {}",
+                        name,
+                        "id, status, task, text, tags, priority, target, due, repeat, created, selected, completed, updated, added_by, checked_by, url, project"
+                    ),
+                };
+                rendered.push_str(&value);
+            }
+            c => rendered.push(c),
+        }
+    }
+    Ok(rendered)
+}
+
+/// Read a date in a view the way `--due-before` reads one, so views can
+/// use relative dates such as `+2d`.
+fn deserialize_date<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<NaiveDate>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|date| parse_date(&date).map_err(de::Error::custom))
+        .transpose()
+}
+
+/// Check that ids with `prefix` can be read back by `parse_id`.
+fn check_id_prefix(prefix: &str) -> Result<()> {
+    let valid = Regex::new(r"^([A-Za-z]\w*-)?$").expect("id prefix pattern is a valid regex");
+    if !valid.is_match(prefix) {
+        bail!(
+            "invalid id prefix `{}`, it must start with a letter and end in `-`",
+            prefix
+        );
+    }
+    Ok(())
+}
+
+fn deserialize_id_prefix<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let prefix = String::deserialize(deserializer)?;
+    check_id_prefix(&prefix).map_err(de::Error::custom)?;
+    Ok(prefix)
+}
+
+fn deserialize_since<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<NaiveDate>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|since| parse_since(&since).map_err(de::Error::custom))
+        .transpose()
+}
+
+fn parse_since(since: &str) -> Result<NaiveDate> {
+    let span = since.len() > 1
+        && since.ends_with(['d', 'w'])
+        && since[..since.len() - 1].chars().all(|c| c.is_ascii_digit());
+    if span {
+        parse_date(&format!("-{}", since))
+    } else {
+        parse_date(since)
+    }
+}
+
+/// Lines of a task file with `count` tasks spread over the sections,
+/// with a share of them carrying tags, priorities and due dates.
+fn bench_lines(count: usize) -> Vec<String> {
+    let today = Local::now().date_naive();
+    let mut sections: BTreeMap<u8, Vec<String>> = BTreeMap::new();
+    for id in 0..count {
+        let (order, status) = match id % 10 {
+            0 => (0, TaskStatus::Selected),
+            1..=3 => (2, TaskStatus::Complete),
+            _ => (1, TaskStatus::Incomplete),
+        };
+        let mut task = Task::new(id, format!("generated task number {}", id), status);
+        if id % 3 == 0 {
+            task.tags.push(format!("tag{}", id % 7));
+        }
+        if id % 5 == 0 {
+            task.due = Some(today + chrono::Duration::days((id % 60) as i64 - 30));
+        }
+        task.created = Some(today);
+        sections.entry(order).or_default().push(task.to_markdown());
+    }
+    let mut lines = vec![];
+    for (order, section) in ["SELECTED", "INCOMPLETE", "COMPLETE"].iter().enumerate() {
+        lines.push(format!("### {}", section));
+        lines.push(String::new());
+        lines.extend(sections.remove(&(order as u8)).unwrap_or_default());
+        lines.push(String::new());
+        lines.push(String::from("---"));
+        lines.push(String::new());
+    }
+    lines
+}
+
+fn median(mut times: Vec<Duration>) -> Duration {
+    times.sort();
+    times.get(times.len() / 2).copied().unwrap_or_default()
+}
+
+fn run_bench(count: usize, runs: usize) -> Result<Vec<(&'static str, Duration)>> {
+    let path = env::temp_dir().join(format!("markdone-bench-{}.md", process::id()));
+    let result = time_bench_steps(&path, &bench_lines(count), runs);
+    fs::remove_file(&path).ok();
+    result
+}
+
+fn time_bench_steps(
+    path: &PathBuf,
+    lines: &[String],
+    runs: usize,
+) -> Result<Vec<(&'static str, Duration)>> {
+    let mut steps: Vec<(&'static str, Vec<Duration>)> = vec![
+        ("write", vec![]),
+        ("parse", vec![]),
+        ("list", vec![]),
+        ("add", vec![]),
+        ("check", vec![]),
+    ];
+    for _ in 0..runs.max(1) {
+        let start = Instant::now();
+        write_lines(path, lines)?;
+        steps[0].1.push(start.elapsed());
+
+        let start = Instant::now();
+        let tasks = TaskList::load(path)?;
+        steps[1].1.push(start.elapsed());
+
+        let start = Instant::now();
+        let listed: Vec<String> = tasks
+            .iter()
+            .filter(|t| t.task_status != TaskStatus::Complete)
+            .map(|t| t.to_string())
+            .collect();
+        steps[2].1.push(start.elapsed());
+        drop(listed);
+
+        let start = Instant::now();
+        let mut tasks = TaskList::load(path)?;
+        tasks.add(String::from("benchmark task"), TaskStatus::Incomplete);
+        tasks.save()?;
+        steps[3].1.push(start.elapsed());
+
+        let start = Instant::now();
+        let mut tasks = TaskList::load(path)?;
+        if let Some(id) = tasks.next_selected().map(|t| t.id) {
+            tasks.check(id)?;
+        }
+        tasks.save()?;
+        steps[4].1.push(start.elapsed());
+    }
+    Ok(steps
+        .into_iter()
+        .map(|(name, times)| (name, median(times)))
+        .collect())
+}
+
+fn sparkline(counts: &[usize]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let max = counts.iter().copied().max().unwrap_or(0).max(1);
+    counts
+        .iter()
+        .map(|count| BARS[count * (BARS.len() - 1) / max])
+        .collect()
+}
+
+/// A grid of tasks completed per day for the weeks of the last year, one
+/// row per weekday, with month names above the columns.
+fn heatmap_lines(
+    completed: &BTreeMap<NaiveDate, usize>,
+    start: NaiveDate,
+    dates: &DateStyle,
+) -> Vec<String> {
+    const LEVELS: [char; 5] = ['·', '░', '▒', '▓', '█'];
+    let today = Local::now().date_naive();
+    let weeks = ((today - start).num_days() / 7 + 1) as usize;
+    let max = completed.values().copied().max().unwrap_or(0).max(1);
+    let labels: Vec<String> = (0..7)
+        .map(|day| dates.format_as(start + chrono::Duration::days(day), "%a"))
+        .collect();
+    let width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0) + 1;
+    let mut months: Vec<char> = vec![' '; weeks];
+    // Each month is named above the first week that starts in it, unless
+    // the name before it is still in the way.
+    let mut free = 0;
+    for week in 0..weeks {
+        let first = start + chrono::Duration::weeks(week as i64);
+        let name: Vec<char> = dates.format_as(first, "%b").chars().collect();
+        if first.day0() < 7 && week >= free && week + name.len() <= weeks {
+            months[week..week + name.len()].copy_from_slice(&name);
+            free = week + name.len() + 1;
+        }
+    }
+    let mut lines = vec![format!(
+        "{:width$}{}",
+        "",
+        months.iter().collect::<String>().trim_end(),
+        width = width
+    )];
+    for (day, label) in labels.iter().enumerate() {
+        let cells: String = (0..weeks)
+            .map(|week| start + chrono::Duration::days((week * 7 + day) as i64))
+            .take_while(|date| *date <= today)
+            .map(|date| {
+                let count = completed.get(&date).copied().unwrap_or(0);
+                LEVELS[(count * (LEVELS.len() - 1)).div_ceil(max)]
+            })
+            .collect();
+        lines.push(format!("{:width$}{}", label, cells, width = width));
+    }
+    lines.push(format!(
+        "{:width$}less {} more",
+        "",
+        LEVELS.iter().collect::<String>(),
+        width = width
+    ));
+    lines
+}
+
+/// When the file at `path` was last modified, if it exists.
+fn modified_at(path: &Path, dates: &DateStyle) -> Option<String> {
+    let modified: DateTime<Local> = fs::metadata(path).and_then(|m| m.modified()).ok()?.into();
+    Some(format!(
+        "{} {}",
+        dates.format(modified.date_naive()),
+        modified.format("%H:%M")
+    ))
+}
+
+fn status_lines(
+    path: &Path,
+    tasks: &TaskList,
+    ids: &IdStyle,
+    dates: &DateStyle,
+    hyperlinks: bool,
+    color: bool,
+) -> Vec<String> {
+    let file = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let count = |section: TaskStatus| tasks.iter().filter(|t| t.task_status == section).count();
+    let mut lines = vec![
+        format!("file: {}", file.display()),
+        format!(
+            "tasks: {} selected, {} incomplete, {} complete",
+            count(TaskStatus::Selected),
+            count(TaskStatus::Incomplete),
+            count(TaskStatus::Complete)
+        ),
+        format!(
+            "overdue: {}, missed targets: {}",
+            tasks.iter().filter(|t| t.is_overdue()).count(),
+            tasks.iter().filter(|t| t.missed_target()).count()
+        ),
+    ];
+    let next: Vec<&Task> = tasks
+        .iter()
+        .filter(|t| t.task_status == TaskStatus::Selected)
+        .take(3)
+        .collect();
+    if !next.is_empty() {
+        lines.push(String::from("next up:"));
+        lines.extend(next.iter().map(|t| {
+            format!(
+                "  {}\t{}",
+                ids.format(t.id),
+                t.render_text(hyperlinks, color, dates)
+            )
+        }));
+    }
+    if let Some(changed) = modified_at(path, dates) {
+        lines.push(format!("last change: {}", changed));
+    }
+    let mut backup = file.into_os_string();
+    backup.push(".bak");
+    let backup = modified_at(Path::new(&backup), dates);
+    lines.push(format!(
+        "last backup: {}",
+        backup.as_deref().unwrap_or("never")
+    ));
+    lines
+}
+
+fn print_stats(
+    tasks: &[Task],
+    archived: usize,
+    since: NaiveDate,
+    oldest: usize,
+    output: OutputMode,
+    dates: &DateStyle,
+    limits: &SectionLimits,
+) {
+    let today = Local::now().date_naive();
+    let count = |section: TaskStatus| tasks.iter().filter(|t| t.task_status == section).count();
+    let complete = count(TaskStatus::Complete);
+    for section in TaskStatus::all() {
+        let count = count(section);
+        let mut notes = vec![];
+        // Archived tasks count as complete but are not in the task file
+        // that the limits apply to.
+        let mut in_file = count;
+        if section == TaskStatus::Complete && archived > 0 {
+            notes.push(format!("{} archived", archived));
+            in_file -= archived;
+        }
+        match limits.get(&section) {
+            Some(limit) if in_file > limit => {
+                notes.push(format!("limit {}, over by {}", limit, in_file - limit))
+            }
+            Some(limit) => notes.push(format!("limit {}", limit)),
+            None => {}
+        }
+        if notes.is_empty() {
+            println!("{}: {}", section, count);
+        } else {
+            println!("{}: {} ({})", section, count, notes.join(", "));
+        }
+    }
+    if let Some(percent) = (complete * 100).checked_div(tasks.len()) {
+        if output == OutputMode::ScreenReader {
+            println!("progress: {}% complete", percent);
+        } else {
+            let filled = percent / 5;
+            println!(
+                "progress: {}{} {}%",
+                "█".repeat(filled),
+                "░".repeat(20 - filled),
+                percent
+            );
+        }
+    }
+
+    let done: Vec<&Task> = tasks
+        .iter()
+        .filter(|t| t.task_status == TaskStatus::Complete)
+        .filter(|t| t.completed.is_some_and(|d| d >= since))
+        .collect();
+    println!("completed since {}: {}", dates.format(since), done.len());
+    let days: Vec<NaiveDate> = since.iter_days().take_while(|d| *d <= today).collect();
+    let per_day: Vec<usize> = days
+        .iter()
+        .map(|day| done.iter().filter(|t| t.completed == Some(*day)).count())
+        .collect();
+    if output == OutputMode::ScreenReader {
+        for (day, count) in days.iter().zip(per_day.iter()).filter(|(_, c)| **c > 0) {
+            println!("completed on {}: {}", dates.format(*day), count);
+        }
+    } else if !days.is_empty() && days.len() <= 31 {
+        println!("per day: {}", sparkline(&per_day));
+    }
+    let mut per_week: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+    for t in done.iter() {
+        if let Some(day) = t.completed {
+            *per_week.entry(dates.week_of(day)).or_default() += 1;
+        }
+    }
+    if !per_week.is_empty() {
+        let weeks: Vec<String> = per_week
+            .iter()
+            .map(|(week, count)| format!("{} {}", dates.format(*week), count))
+            .collect();
+        println!("per week: {}", weeks.join(", "));
+    }
+    let spans: Vec<i64> = done
+        .iter()
+        .filter_map(|t| Some((t.completed? - t.created?).num_days()))
+        .collect();
+    if !spans.is_empty() {
+        println!(
+            "average time to complete: {:.1} days over {} tasks",
+            spans.iter().sum::<i64>() as f64 / spans.len() as f64,
+            spans.len()
+        );
+    }
+
+    let mut open: Vec<&Task> = tasks
+        .iter()
+        .filter(|t| t.task_status != TaskStatus::Complete && t.created.is_some())
+        .collect();
+    open.sort_by_key(|t| t.created);
+    if oldest > 0 && !open.is_empty() {
+        println!("oldest open tasks:");
+        for t in open.into_iter().take(oldest) {
+            let age = t.created.map_or(0, |created| (today - created).num_days());
+            println!("  {}\t{} days\t{}", t.id, age, t.task);
+        }
+    }
+}
+
+fn badge_svg(open: usize, done: usize) -> String {
+    let total = open + done;
+    let (message, color) = match (done * 100).checked_div(total) {
+        None => ("no tasks".to_string(), "#9f9f9f"),
+        Some(percent) => {
+            let color = match percent {
+                80.. => "#4c1",
+                50.. => "#dfb317",
+                _ => "#e05d44",
+            };
+            (
+                format!("{} open, {} done ({}%)", open, done, percent),
+                color,
+            )
+        }
+    };
+    let label = "tasks";
+    // Rough Verdana 11px advance, close enough for badge layout.
+    let label_width = label.len() * 7 + 10;
+    let message_width = message.len() * 7 + 10;
+    let width = label_width + message_width;
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {message}">
+  <title>{label}: {message}</title>
+  <clipPath id="r"><rect width="{width}" height="20" rx="3" fill="#fff"/></clipPath>
+  <g clip-path="url(#r)">
+    <rect width="{label_width}" height="20" fill="#555"/>
+    <rect x="{label_width}" width="{message_width}" height="20" fill="{color}"/>
+  </g>
+  <g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">
+    <text x="{label_x}" y="14">{label}</text>
+    <text x="{message_x}" y="14">{message}</text>
+  </g>
+</svg>
+"##,
+        label_x = label_width / 2,
+        message_x = label_width + message_width / 2,
+    )
+}
+
+fn find_conflicts(lines: &[String]) -> Vec<String> {
+    let mut problems = vec![];
+    let mut section: Option<TaskStatus> = None;
+    let mut headers: Vec<TaskStatus> = vec![];
+    let mut tasks: Vec<(usize, Task)> = vec![];
+    for (i, line) in lines.iter().enumerate() {
+        let number = i + 1;
+        if ["<<<<<<<", "=======", ">>>>>>>", "|||||||"]
+            .iter()
+            .any(|m| line.starts_with(m))
+        {
+            problems.push(format!(
+                "line {}: unresolved merge conflict marker, resolve the conflict in an editor",
+                number
+            ));
+        } else if let Ok(s) = TaskStatus::try_from(line) {
+            if headers.contains(&s) {
+                problems.push(format!(
+                    "line {}: duplicate `{}` section, move its tasks into the first one",
+                    number, s
+                ));
+            }
+            headers.push(s);
+            section = Some(s);
+        } else if line.starts_with("- [") {
+            match section.map(|s| Task::try_from((line.clone(), s))) {
+                Some(Ok(task)) => tasks.push((number, task)),
+                Some(Err(_)) => problems.push(format!(
+                    "line {}: could not parse task, fix it or move it to the right section",
+                    number
+                )),
+                None => problems.push(format!(
+                    "line {}: task is outside of any section, move it into one",
+                    number
+                )),
+            }
+        }
+    }
+    for s in TaskStatus::all() {
+        if !headers.contains(&s) {
+            problems.push(format!("missing `{}` section, add it back", s));
+        }
+    }
+    for (i, (number, task)) in tasks.iter().enumerate() {
+        for (other_number, other) in tasks[i + 1..].iter() {
+            if task.id == other.id {
+                problems.push(format!(
+                    "lines {} and {}: id `{}` is used by more than one task, delete or renumber one of them",
+                    number, other_number, task.id
+                ));
+            } else if task.task_status != other.task_status
+                && normalize(&task.task) == normalize(&other.task)
+            {
+                problems.push(format!(
+                    "lines {} and {}: task `{}` is both {} and {}, use `merge-tasks {} {}` to keep one",
+                    number, other_number, task.task, task.task_status, other.task_status, task.id, other.id
+                ));
+            }
+        }
+    }
+    problems
+}
+
+fn lint(lines: &[String], rules: &LintRules) -> Vec<(usize, &'static str, String)> {
+    let mut problems = vec![];
+    let mut section: Option<TaskStatus> = None;
+    let mut selected_header = 0;
+    let mut selected = 0;
+    for (i, line) in lines.iter().enumerate() {
+        let number = i + 1;
+        if let Ok(s) = TaskStatus::try_from(line) {
+            if s == TaskStatus::Selected {
+                selected_header = number;
+            }
+            section = Some(s);
+            continue;
+        }
+        let task = match section.map(|s| Task::try_from((line.clone(), s))) {
+            Some(Ok(task)) => task,
+            _ => continue,
+        };
+        if task.task_status == TaskStatus::Selected {
+            selected += 1;
+        }
+        if task.task_status == TaskStatus::Complete {
+            continue;
+        }
+        if rules.enabled("high-priority-no-due")
+            && task.priority == Some(Priority::High)
+            && task.due.is_none()
+        {
+            problems.push((
+                number,
+                "high-priority-no-due",
+                format!("high priority task {} has no due date", task.id),
+            ));
+        }
+        let length = task.task.chars().count();
+        if rules.enabled("long-text") && length > rules.max_length {
+            problems.push((
+                number,
+                "long-text",
+                format!(
+                    "task {} text is {} characters, over the limit of {}",
+                    task.id, length, rules.max_length
+                ),
+            ));
+        }
+        if rules.enabled("forbidden-words") {
+            let text = task.task.to_lowercase();
+            for word in rules.forbidden_words.iter() {
+                let found = text
+                    .split(|c: char| !c.is_alphanumeric())
+                    .any(|w| w == word.to_lowercase());
+                if found {
+                    problems.push((
+                        number,
+                        "forbidden-words",
+                        format!("task {} contains forbidden word `{}`", task.id, word),
+                    ));
+                }
+            }
+        }
+    }
+    if let Some(max) = rules.max_selected {
+        if rules.enabled("too-many-selected") && selected > max {
+            problems.push((
+                selected_header,
+                "too-many-selected",
+                format!("{} tasks are selected, over the limit of {}", selected, max),
+            ));
+        }
+    }
+    problems.sort_by_key(|(line, _, _)| *line);
+    problems
+}
+
+/// Directory under `kind`, next to the task file at `path`, that keeps
+/// state for that file.
+fn state_dir(path: &Path, kind: &str) -> PathBuf {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path.file_name().unwrap_or(path.as_os_str());
+    dir.join(kind).join(name)
+}
+
+fn history_dir(path: &Path) -> PathBuf {
+    state_dir(path, HISTORY_DIR)
+}
+
+/// Fail while a focus session started with `--lock` is running. Locks
+/// left behind by sessions that were killed expire when the session
+/// would have ended.
+fn check_focus_lock(path: &Path) -> Result<()> {
+    let lock = state_dir(path, FOCUS_DIR).join("lock");
+    let contents = match fs::read_to_string(&lock) {
+        Ok(contents) => contents,
+        Err(_) => return Ok(()),
+    };
+    let (id, until) = contents
+        .trim()
+        .split_once('\t')
+        .with_context(|| format!("invalid focus lock `{:?}`", lock))?;
+    let until = DateTime::parse_from_rfc3339(until)
+        .with_context(|| format!("invalid focus lock `{:?}`", lock))?;
+    if Local::now() < until {
+        bail!(
+            "task file is locked by a focus session on task with id `{}` until {}",
+            id,
+            until.with_timezone(&Local).format("%H:%M")
+        );
+    }
+    Ok(())
+}
+
+/// Run a focus session on `task`, showing a countdown until `length` has
+/// passed or `q` is pressed. Returns how long the session lasted.
+fn run_focus(task: &Task, length: Duration) -> Result<Duration> {
+    let start = Instant::now();
+    let interactive = stdin().is_terminal() && terminal::enable_raw_mode().is_ok();
+    let result = (|| -> Result<()> {
+        let mut handle = stderr().lock();
+        while let Some(remaining) = length.checked_sub(start.elapsed()) {
+            let secs = remaining.as_secs();
+            write!(
+                handle,
+                "\r{:02}:{:02}  {}: {}",
+                secs / 60,
+                secs % 60,
+                task.id,
+                task.task
+            )?;
+            handle.flush()?;
+            let wait = remaining.min(Duration::from_secs(1));
+            if !interactive {
+                thread::sleep(wait);
+            } else if event::poll(wait)? {
+                if let Event::Key(key) = event::read()? {
+                    let stop = key.code == KeyCode::Char('q')
+                        || key.modifiers.contains(KeyModifiers::CONTROL)
+                            && key.code == KeyCode::Char('c');
+                    if key.kind == KeyEventKind::Press && stop {
+                        break;
+                    }
+                }
+            }
+        }
+        write!(handle, "\r\n")?;
+        Ok(())
+    })();
+    if interactive {
+        terminal::disable_raw_mode()?;
+    }
+    result?;
+    Ok(start.elapsed().min(length))
+}
+
+/// Append a finished focus session to the session log of the task file.
+fn log_focus_session(
+    path: &Path,
+    task: &Task,
+    started: DateTime<Local>,
+    length: Duration,
+) -> Result<()> {
+    let dir = state_dir(path, FOCUS_DIR);
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("could not create focus directory `{:?}`", dir))?;
+    let log = dir.join("log");
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log)
+        .with_context(|| format!("could not open focus log `{:?}`", log))?;
+    writeln!(
+        file,
+        "{}\t{}\t{}\t{}",
+        started.to_rfc3339(),
+        task.id,
+        length.as_secs() / 60,
+        task.task
+    )
+    .with_context(|| format!("could not write to focus log `{:?}`", log))
+}
+
+/// Number of logged focus sessions on task `id` and their total minutes.
+fn focus_time(path: &Path, id: usize) -> (usize, u64) {
+    let log = state_dir(path, FOCUS_DIR).join("log");
+    let contents = fs::read_to_string(log).unwrap_or_default();
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t').skip(1);
+            let task: usize = fields.next()?.parse().ok()?;
+            let minutes: u64 = fields.next()?.parse().ok()?;
+            Some((task, minutes))
+        })
+        .filter(|(task, _)| *task == id)
+        .fold((0, 0), |(count, total), (_, minutes)| {
+            (count + 1, total + minutes)
+        })
+}
+
+fn snapshots(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.is_dir() {
+        return Ok(vec![]);
+    }
+    let mut snapshots: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("could not read history directory `{:?}`", dir))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "md"))
+        .collect();
+    snapshots.sort();
+    Ok(snapshots)
+}
+
+fn save_snapshot(dir: &Path, contents: &str) -> Result<()> {
+    fs::create_dir_all(dir)
+        .with_context(|| format!("could not create history directory `{:?}`", dir))?;
+    let file = dir.join(format!("{}.md", Local::now().format("%Y%m%dT%H%M%S%.9f")));
+    fs::write(&file, contents).with_context(|| format!("could not write to file `{:?}`", file))
+}
+
+fn record_history(path: &Path, before: &str, after: &str, limit: usize) -> Result<()> {
+    if after == before {
+        return Ok(());
+    }
+    let dir = history_dir(path);
+    save_snapshot(&dir, before)?;
+    let redo = dir.join("redo");
+    if redo.is_dir() {
+        fs::remove_dir_all(&redo)
+            .with_context(|| format!("could not clear history directory `{:?}`", redo))?;
+    }
+    let snapshots = snapshots(&dir)?;
+    for old in snapshots.iter().take(snapshots.len().saturating_sub(limit)) {
+        fs::remove_file(old).with_context(|| format!("could not remove file `{:?}`", old))?;
+    }
+    Ok(())
+}
 
-            let id = get_next_id(&lines);
+fn step_history(path: &Path, from: &Path, to: &Path) -> Result<bool> {
+    let snapshot = match snapshots(from)?.pop() {
+        Some(snapshot) => snapshot,
+        None => return Ok(false),
+    };
+    let current =
+        fs::read_to_string(path).with_context(|| format!("could not read file `{:?}`", path))?;
+    let contents = fs::read_to_string(&snapshot)
+        .with_context(|| format!("could not read file `{:?}`", snapshot))?;
+    save_snapshot(to, &current)?;
+    write_lines(
+        path,
+        &contents.lines().map(String::from).collect::<Vec<_>>(),
+    )?;
+    fs::remove_file(&snapshot)
+        .with_context(|| format!("could not remove file `{:?}`", snapshot))?;
+    Ok(true)
+}
+
+/// Where each file in a bundle belongs for the task file `path`.
+fn bundle_files(path: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let mut files = vec![
+        (String::from(BUNDLE_TASKS), path.to_path_buf()),
+        (String::from(BUNDLE_ARCHIVE), archive_path(path)),
+        (
+            String::from(BUNDLE_CONFIG),
+            find_config().unwrap_or_else(|| PathBuf::from(CONFIG_FILE)),
+        ),
+        (String::from(BUNDLE_PROJECTS), projects_path()?),
+    ];
+    let history = history_dir(path);
+    for (name, dir) in [
+        ("history", history.clone()),
+        ("history/redo", history.join("redo")),
+    ] {
+        for snapshot in snapshots(&dir)? {
+            let file = snapshot.file_name().unwrap_or_default().to_string_lossy();
+            files.push((format!("{}/{}", name, file), snapshot));
+        }
+    }
+    Ok(files)
+}
+
+fn export_bundle(path: &Path, file: &Path) -> Result<usize> {
+    if !path.exists() {
+        bail!("could not find task file `{:?}`", path);
+    }
+    let mut entries = vec![];
+    for (name, source) in bundle_files(path)? {
+        if source.is_file() {
+            let contents =
+                fs::read(&source).with_context(|| format!("could not read file `{:?}`", source))?;
+            entries.push((name, contents));
+        }
+    }
+    let manifest: String = entries
+        .iter()
+        .map(|(name, contents)| format!("{:x}  {}\n", Sha256::digest(contents), name))
+        .collect();
+    let output =
+        fs::File::create(file).with_context(|| format!("could not create file `{:?}`", file))?;
+    let encoder = zstd::Encoder::new(output, 0)?.auto_finish();
+    let mut builder = tar::Builder::new(encoder);
+    let manifest = (String::from(BUNDLE_MANIFEST), manifest.into_bytes());
+    for (name, contents) in entries.iter().chain([&manifest]) {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, name, contents.as_slice())
+            .with_context(|| format!("could not write bundle `{:?}`", file))?;
+    }
+    builder
+        .into_inner()
+        .with_context(|| format!("could not write bundle `{:?}`", file))?;
+    Ok(entries.len())
+}
 
-            if (section_end - section_start) == 2 {
-                lines.insert(section_end, String::from(""));
+fn import_bundle(path: &Path, file: &Path, force: bool, allow_symlink: bool) -> Result<usize> {
+    let input =
+        fs::File::open(file).with_context(|| format!("could not open file `{:?}`", file))?;
+    let mut archive = tar::Archive::new(zstd::Decoder::new(input)?);
+    let mut entries = BTreeMap::new();
+    for entry in archive
+        .entries()
+        .with_context(|| format!("could not read bundle `{:?}`", file))?
+    {
+        let mut entry = entry.with_context(|| format!("could not read bundle `{:?}`", file))?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+        let mut contents = vec![];
+        entry
+            .read_to_end(&mut contents)
+            .with_context(|| format!("could not read `{}` from bundle `{:?}`", name, file))?;
+        entries.insert(name, contents);
+    }
+    let manifest = entries
+        .remove(BUNDLE_MANIFEST)
+        .with_context(|| format!("bundle `{:?}` has no manifest", file))?;
+    let manifest = String::from_utf8(manifest).context("bundle manifest is not valid UTF-8")?;
+    let mut listed = 0;
+    for line in manifest.lines() {
+        let (hash, name) = line
+            .split_once("  ")
+            .with_context(|| format!("invalid manifest line `{}`", line))?;
+        let contents = entries
+            .get(name)
+            .with_context(|| format!("bundle is missing `{}` listed in its manifest", name))?;
+        if format!("{:x}", Sha256::digest(contents)) != hash {
+            bail!("checksum of `{}` does not match the bundle manifest", name);
+        }
+        listed += 1;
+    }
+    if listed != entries.len() {
+        bail!("bundle `{:?}` has files not listed in its manifest", file);
+    }
+    let files = bundle_files(path)?;
+    let history = history_dir(path);
+    let mut targets = vec![];
+    for (name, contents) in entries {
+        let target = match name.strip_prefix("history/") {
+            Some(snapshot) => {
+                let inside = Path::new(snapshot)
+                    .components()
+                    .all(|c| matches!(c, Component::Normal(_)));
+                if !inside {
+                    bail!("bundle contains unexpected file `{}`", name);
+                }
+                history.join(snapshot)
             }
-            lines.insert(
-                section_start + 2,
-                format!("- [{}] **{}**: {}", completed, id, task),
+            None => files
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, target)| target.clone())
+                .with_context(|| format!("bundle contains unexpected file `{}`", name))?,
+        };
+        if target.exists() && !force {
+            bail!(
+                "file `{:?}` already exists\nuse `--force` to replace it",
+                target
             );
-            let mut file = OpenOptions::new().write(true).open(path)?;
-            file.seek(SeekFrom::Start(0))?;
-            for line in lines {
-                writeln!(file, "{}", line)?;
+        }
+        let task_file = name == BUNDLE_TASKS || name == BUNDLE_ARCHIVE;
+        if task_file && !allow_symlink {
+            check_task_path(&target)?;
+        }
+        targets.push((task_file, target, contents));
+    }
+    for (task_file, target, contents) in &targets {
+        if let Some(dir) = target.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("could not create directory `{:?}`", dir))?;
+        }
+        if *task_file {
+            let text = String::from_utf8_lossy(contents);
+            let lines: Vec<String> = text.lines().map(String::from).collect();
+            write_lines(target, &lines)
+        } else {
+            fs::write(target, contents).map_err(Into::into)
+        }
+        .with_context(|| format!("could not write to file `{:?}`", target))?;
+    }
+    Ok(targets.len())
+}
+
+/// The user's markdone config directory, e.g. `~/.config/markdone`.
+fn config_dir() -> Result<PathBuf> {
+    let dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => match env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(".config"),
+            None => PathBuf::from(
+                env::var_os("APPDATA").context("could not find user config directory")?,
+            ),
+        },
+    };
+    Ok(dir.join("markdone"))
+}
+
+fn projects_path() -> Result<PathBuf> {
+    Ok(config_dir()?.join(PROJECTS_FILE))
+}
+
+fn load_projects() -> Result<Projects> {
+    let path = projects_path()?;
+    if !path.exists() {
+        return Ok(Projects::default());
+    }
+    let contents = fs::read_to_string(&path)
+        .with_context(|| format!("could not read projects file `{:?}`", path))?;
+    toml::from_str(&contents).with_context(|| format!("could not parse projects file `{:?}`", path))
+}
+
+fn save_projects(projects: &Projects) -> Result<()> {
+    let path = projects_path()?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("could not create directory `{:?}`", dir))?;
+    }
+    let contents = toml::to_string(projects)?;
+    write_lines(
+        &path,
+        &contents.lines().map(String::from).collect::<Vec<_>>(),
+    )
+}
+
+/// Find the nearest `.markdone.toml` above the current directory, falling
+/// back to the global config written by `setup`.
+fn find_config() -> Option<PathBuf> {
+    let dir = env::current_dir().ok()?;
+    dir.ancestors()
+        .map(|d| d.join(CONFIG_FILE))
+        .find(|p| p.is_file())
+        .or_else(|| {
+            config_dir()
+                .ok()
+                .map(|d| d.join(GLOBAL_CONFIG_FILE))
+                .filter(|p| p.is_file())
+        })
+}
+
+/// Print `question` and read an answer, falling back to `default` when
+/// the answer is empty or stdin has ended.
+fn ask(question: &str, default: &str) -> Result<String> {
+    if default.is_empty() {
+        eprint!("{} ", question);
+    } else {
+        eprint!("{} ({}) ", question, default);
+    }
+    stderr().flush()?;
+    let mut answer = String::new();
+    stdin().read_line(&mut answer)?;
+    let answer = answer.trim();
+    Ok(if answer.is_empty() { default } else { answer }.to_string())
+}
+
+/// Ask until the answer is one of `choices`, the first being the default.
+fn ask_choice(question: &str, choices: &[&str]) -> Result<String> {
+    loop {
+        let answer =
+            ask(&format!("{} [{}]", question, choices.join("/")), choices[0])?.to_lowercase();
+        if choices.contains(&answer.as_str()) {
+            return Ok(answer);
+        }
+        eprintln!("please answer one of: {}", choices.join(", "));
+    }
+}
+
+fn setup(force: bool, quiet: bool) -> Result<()> {
+    let location = ask_choice(
+        "Keep tasks in a markdone.md in each project, or in one global file?",
+        &["project", "global"],
+    )?;
+    let mut settings = toml::Table::new();
+    let (config_path, task_file) = if location == "global" {
+        let dir = config_dir()?;
+        let task_file = dir.join(DEFAULT_TASK_FILE);
+        settings.insert(
+            String::from("file"),
+            toml::Value::String(task_file.display().to_string()),
+        );
+        (dir.join(GLOBAL_CONFIG_FILE), task_file)
+    } else {
+        (PathBuf::from(CONFIG_FILE), PathBuf::from(DEFAULT_TASK_FILE))
+    };
+    if config_path.exists() && !force {
+        bail!(
+            "config file `{:?}` already exists\nuse `--force` to replace it",
+            config_path
+        );
+    }
+    let color = ask_choice("Color task output?", &["auto", "always", "never"])?;
+    if color != "auto" {
+        settings.insert(String::from("color"), toml::Value::String(color));
+    }
+    let questions = [
+        (
+            "journal",
+            "Group complete tasks under the day they were checked?",
+        ),
+        (
+            "backup",
+            "Copy the task file to a .bak file before each change?",
+        ),
+    ];
+    for (key, question) in questions {
+        let answer = ask(&format!("{} [y/N]", question), "")?.to_lowercase();
+        if matches!(answer.as_str(), "y" | "yes") {
+            settings.insert(String::from(key), toml::Value::Boolean(true));
+        }
+    }
+    let prefix = ask("Prefix for task ids, e.g. `PROJ-`, empty for none?", "")?;
+    check_id_prefix(&prefix)?;
+    if !prefix.is_empty() {
+        let mut ids = toml::Table::new();
+        ids.insert(String::from("prefix"), toml::Value::String(prefix));
+        settings.insert(String::from("ids"), toml::Value::Table(ids));
+    }
+    let contents = toml::to_string(&settings)?;
+    if let Some(dir) = config_path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("could not create directory `{:?}`", dir))?;
+    }
+    write_lines(
+        &config_path,
+        &contents.lines().map(String::from).collect::<Vec<_>>(),
+    )?;
+    if !quiet {
+        eprintln!("successfully wrote config to `{:?}`", config_path);
+    }
+    if !task_file.exists() {
+        if let Some(dir) = task_file.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("could not create directory `{:?}`", dir))?;
+        }
+        write_lines(&task_file, &TaskList::parse(&task_file, vec![]).to_lines())
+            .with_context(|| format!("could not create file `{:?}`", task_file))?;
+        if !quiet {
+            eprintln!("successfully created `{:?}`", task_file);
+        }
+    }
+    if !quiet {
+        eprintln!("add shell integration with `markdone shell-init <shell>`");
+    }
+    Ok(())
+}
+
+/// The directory holding the config file in use, or the current directory.
+fn project_root() -> Result<PathBuf> {
+    let root = match find_config().as_deref().and_then(Path::parent) {
+        Some(dir) => dir.to_path_buf(),
+        None => env::current_dir().context("could not find current directory")?,
+    };
+    Ok(fs::canonicalize(&root).unwrap_or(root))
+}
+
+/// Refuse a task file inside the project directory that symlinks, itself
+/// or through a parent directory, to a file outside of it. Paths that point
+/// outside the project directory to begin with are left alone.
+fn check_task_path(path: &Path) -> Result<()> {
+    let root = project_root()?;
+    let absolute = env::current_dir()
+        .context("could not find current directory")?
+        .join(path);
+    let inside =
+        absolute.starts_with(&root) && !path.components().any(|c| c == Component::ParentDir);
+    let target = match fs::canonicalize(path) {
+        Ok(target) if inside => target,
+        _ => return Ok(()),
+    };
+    if !target.starts_with(&root) {
+        bail!(
+            "task file `{:?}` leads to `{:?}` outside the project directory `{:?}`\nuse `--allow-symlink` to follow it anyway",
+            path,
+            target,
+            root
+        );
+    }
+    Ok(())
+}
+
+fn load_config() -> Result<Config> {
+    match find_config() {
+        Some(path) => {
+            let contents = fs::read_to_string(&path)
+                .with_context(|| format!("could not read config file `{:?}`", path))?;
+            toml::from_str(&contents)
+                .with_context(|| format!("could not parse config file `{:?}`", path))
+        }
+        None => Ok(Config::default()),
+    }
+}
+
+fn text_pattern(text: &str) -> Regex {
+    Regex::new(&format!("(?i){}", regex::escape(text))).expect("escaped text is a valid regex")
+}
+
+fn find_match(path: &PathBuf, text: &str, sections: &[TaskStatus]) -> Result<usize> {
+    let pattern = text_pattern(text);
+    let tasks = TaskList::load(path)?;
+    let matches: Vec<&Task> = tasks
+        .iter()
+        .filter(|t| sections.contains(&t.task_status) && pattern.is_match(&t.task))
+        .collect();
+    match matches[..] {
+        [task] => Ok(task.id),
+        [] => bail!("no task matches `{}`", text),
+        _ => bail!(
+            "`{}` matches {} tasks, use an id instead:\n{}",
+            text,
+            matches.len(),
+            matches
+                .iter()
+                .map(|t| t.to_string())
+                .collect::<Vec<String>>()
+                .join("\n")
+        ),
+    }
+}
+
+/// Parse a task id, either plain or as shown with the `ids` config, such
+/// as `012` or `PROJ-12`.
+fn parse_id(id: &str) -> Result<usize> {
+    let number = match id.trim().rsplit_once('-') {
+        Some((prefix, number)) if prefix.starts_with(|c: char| c.is_ascii_alphabetic()) => number,
+        _ => id.trim(),
+    };
+    number
+        .parse()
+        .with_context(|| format!("invalid task id `{}`", id))
+}
+
+fn parse_id_range(ids: &str) -> Result<RangeInclusive<usize>> {
+    let range = Regex::new(r"^\s*((?:[A-Za-z]\w*-)?\d+)\s*-\s*((?:[A-Za-z]\w*-)?\d+)\s*$")?;
+    match range.captures(ids) {
+        Some(captures) => {
+            let (start, end) = (parse_id(&captures[1])?, parse_id(&captures[2])?);
+            if start > end {
+                bail!("invalid range `{}`, start is after end", ids);
             }
-            if !quiet {
+            Ok(start..=end)
+        }
+        None => parse_id(ids).map(|id| id..=id),
+    }
+}
+
+fn flatten_ids(ranges: Vec<RangeInclusive<usize>>) -> Vec<usize> {
+    let mut ids: Vec<usize> = vec![];
+    for id in ranges.into_iter().flatten() {
+        if !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+    ids
+}
+
+/// Add the tasks described by `options`, from add and quick capture.
+fn add_tasks(
+    path: &PathBuf,
+    options: AddOptions,
+    config: &Config,
+    quiet: bool,
+    warnings: bool,
+) -> Result<()> {
+    let section = if options.complete {
+        TaskStatus::Complete
+    } else if options.select {
+        TaskStatus::Selected
+    } else {
+        TaskStatus::Incomplete
+    };
+    let texts = match (options.task, &options.from_file) {
+        (Some(task), _) => vec![(task, section)],
+        (None, Some(file)) => {
+            let contents = fs::read_to_string(file)
+                .with_context(|| format!("could not read file `{:?}`", file))?;
+            parse_list_items(&contents)
+                .into_iter()
+                .map(|(text, checked)| {
+                    (
+                        text,
+                        if checked {
+                            TaskStatus::Complete
+                        } else {
+                            section
+                        },
+                    )
+                })
+                .collect()
+        }
+        (None, None) => stdin()
+            .lines()
+            .map(|line| Ok(line?.trim().to_string()))
+            .filter(|line| !matches!(line, Ok(l) if l.is_empty()))
+            .map(|line| line.map(|l| (l, section)))
+            .collect::<Result<Vec<(String, TaskStatus)>>>()
+            .context("could not read tasks from stdin")?,
+    };
+    let bulk = options.stdin || options.from_file.is_some();
+    let mut tasks = TaskList::load(path)?;
+    tasks.set_author(config.author()?);
+    let mut existing: Vec<String> = tasks.iter().flat_map(|t| t.tags.clone()).collect();
+    existing.sort();
+    existing.dedup();
+    for tag in options.tags.iter().filter(|t| !existing.contains(t)) {
+        let suggestion = suggest_tag(tag, &existing);
+        if options.strict_tags || config.strict_tags {
+            match suggestion {
+                Some(s) => bail!("unknown tag `{}`, did you mean `{}`?", tag, s),
+                None => bail!(
+                    "unknown tag `{}`, existing tags are: {}",
+                    tag,
+                    existing.join(", ")
+                ),
+            }
+        }
+        if let (Some(s), true) = (suggestion, warnings) {
+            eprintln!("warning: new tag `{}`, did you mean `{}`?", tag, s);
+        }
+    }
+    let mut added = vec![];
+    for (task, section) in texts {
+        if !options.force && !config.allow_similar {
+            if let Some(similar) = tasks
+                .iter()
+                .find(|t| t.task_status != TaskStatus::Complete && is_similar(&t.task, &task))
+            {
+                if !bulk {
+                    bail!(
+                        "similar task already exists: {}\nuse `--force` to add it anyway",
+                        similar
+                    );
+                }
+                if warnings {
+                    eprintln!(
+                        "warning: skipped `{}`, similar task exists: {}",
+                        task, similar
+                    );
+                }
+                continue;
+            }
+        }
+        let new_task = tasks.add(task.clone(), section);
+        added.push((task.clone(), new_task.id));
+        new_task.url = options.url.clone();
+        new_task.target = options.target;
+        new_task.due = options.due;
+        for tag in &options.tags {
+            if !new_task.tags.contains(tag) {
+                new_task.tags.push(tag.clone());
+            }
+        }
+        new_task.priority = options.priority;
+        new_task.repeat = options.repeat;
+        if new_task.repeat.is_some() {
+            let date = new_task.due.unwrap_or_else(|| Local::now().date_naive());
+            if let Some(text) = expand_template(&task, date) {
+                new_task.template = Some(task);
+                new_task.task = text;
+            }
+        }
+    }
+    tasks.save()?;
+    if quiet {
+        return Ok(());
+    }
+    match (&options.from_file, added.first(), added.last()) {
+        (Some(file), Some((_, first)), Some((_, last))) => eprintln!(
+            "successfully added {} tasks from `{:?}` with ids `{:?}` to `{:?}`",
+            added.len(),
+            file,
+            first,
+            last
+        ),
+        (Some(file), _, _) => eprintln!("no tasks added from `{:?}`", file),
+        _ => {
+            for (task, id) in added {
                 eprintln!("successfully added task `{:?}` with id `{:?}`", task, id);
             }
         }
-        Commands::Check { id } => {
-            move_task_to_section(id, path, TaskStatus::Complete, vec![TaskStatus::Complete])?;
-            if !quiet {
-                eprintln!("successfully checked task with id `{:?}`", id);
-            }
+    }
+    Ok(())
+}
+
+/// Apply `update` to every task in `ids` and save the file once. Tasks that
+/// fail are reported and skipped so the rest of the batch still goes through.
+fn update_each<F>(path: &PathBuf, ids: &[usize], quiet: bool, mut update: F) -> Result<()>
+where
+    F: FnMut(&mut TaskList, usize) -> Result<String>,
+{
+    let mut tasks = TaskList::load(path)?;
+    let mut messages = vec![];
+    let mut failed = 0;
+    for id in ids.iter() {
+        match update(&mut tasks, *id) {
+            Ok(message) => messages.push(message),
+            Err(error) => {
+                eprintln!("Error: {:#}", error);
+                failed += 1;
+            }
+        }
+    }
+    if !messages.is_empty() {
+        tasks.save()?;
+    }
+    if !quiet {
+        for message in messages {
+            eprintln!("{}", message);
+        }
+    }
+    if failed > 0 {
+        bail!("could not update {} of {} tasks", failed, ids.len());
+    }
+    Ok(())
+}
+
+fn move_task_to_section(
+    tasks: &mut TaskList,
+    id: usize,
+    section: TaskStatus,
+    allowed_sections: Vec<TaskStatus>,
+) -> Result<()> {
+    let task = tasks.get(id)?;
+    if allowed_sections.contains(&task.task_status) {
+        bail!(
+            "cannot move task with id `{:?}` from section `{:?}`",
+            id,
+            task.task_status
+        );
+    }
+    tasks.move_task(id, section)
+}
+
+fn confirm_delete(path: &PathBuf, ids: &[usize], force: bool) -> Result<()> {
+    let tasks = TaskList::load(path)?;
+    let found: Vec<&Task> = ids.iter().filter_map(|id| tasks.get(*id).ok()).collect();
+    let protected = found.iter().filter(|t| t.protected).count();
+    let mut summary = if force || protected == 0 {
+        format!("will delete {} tasks", found.len())
+    } else {
+        format!(
+            "will delete {} tasks, keeping {} protected",
+            found.len() - protected,
+            protected
+        )
+    };
+    if found.len() < ids.len() {
+        summary.push_str(&format!(", {} not found", ids.len() - found.len()));
+    }
+    if !stdin().is_terminal() {
+        bail!("{}, pass `--yes` to confirm", summary);
+    }
+    eprint!("{}, continue? [y/N] ", summary);
+    stderr().flush()?;
+    let mut answer = String::new();
+    stdin().read_line(&mut answer)?;
+    if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+        bail!("cancelled, no tasks were deleted");
+    }
+    Ok(())
+}
+
+fn update_task<F>(path: &PathBuf, id: usize, update: F) -> Result<()>
+where
+    F: FnOnce(&mut Task) -> Result<()>,
+{
+    let mut tasks = TaskList::load(path)?;
+    update(tasks.get_mut(id)?)?;
+    tasks.save()
+}
+
+/// Set or clear the flag that `flag` points to on a task, failing if it
+/// already has that value. `name` is the flag as an adjective, e.g. `pinned`.
+fn set_flag(
+    path: &PathBuf,
+    id: usize,
+    flag: fn(&mut Task) -> &mut bool,
+    value: bool,
+    name: &str,
+    quiet: bool,
+) -> Result<()> {
+    update_task(path, id, |task| {
+        let flag = flag(task);
+        if *flag == value && value {
+            bail!("task with id `{:?}` is already {}", id, name);
+        }
+        if *flag == value {
+            bail!("task with id `{:?}` is not {}", id, name);
+        }
+        *flag = value;
+        Ok(())
+    })?;
+    if !quiet {
+        let prefix = if value { "" } else { "un" };
+        eprintln!("successfully {}{} task with id `{:?}`", prefix, name, id);
+    }
+    Ok(())
+}
+
+fn warn_section_limits(tasks: &[Task], limits: &SectionLimits) {
+    for section in TaskStatus::all() {
+        if let Some(limit) = limits.get(&section) {
+            let count = tasks.iter().filter(|t| t.task_status == section).count();
+            if count > limit {
+                eprintln!(
+                    "warning: `{}` section has {} tasks, over the limit of {}",
+                    section, count, limit
+                );
+            }
+        }
+    }
+}
+
+fn main() {
+    let args = Cli::parse();
+    let json_errors = match &args.command {
+        Commands::List(options) => options.format == Some(ListFormat::Json),
+        Commands::View { format, .. } => *format == Some(ListFormat::Json),
+        Commands::Search(options) => options.list.format == Some(ListFormat::Json),
+        _ => false,
+    };
+    if let Err(error) = run(args) {
+        if json_errors {
+            let causes: Vec<String> = error.chain().skip(1).map(|e| e.to_string()).collect();
+            eprintln!(
+                "{}",
+                serde_json::json!({ "error": error.to_string(), "causes": causes })
+            );
+        } else {
+            eprintln!("Error: {:?}", error);
+        }
+        process::exit(1);
+    }
+}
+
+fn run(args: Cli) -> Result<()> {
+    let mut config = load_config()?;
+    let quiet = args.quiet || args.silent;
+    let dates = config.dates.style()?;
+    let warnings = !args.no_warnings && !args.silent;
+    let hyperlinks = !args.no_hyperlinks && supports_hyperlinks();
+    let color = match args.color.or(config.color).unwrap_or(ColorChoice::Auto) {
+        ColorChoice::Auto => supports_color(),
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+    };
+    let read_only = args.read_only || config.read_only;
+    if read_only && args.command.is_mutating() {
+        bail!("cannot modify tasks in read-only mode");
+    }
+    let project = match args.project {
+        Some(project) => Some(project),
+        None if args.file.is_none() => env::var("MARKDONE_PROJECT").ok().filter(|p| !p.is_empty()),
+        None => None,
+    };
+    let path = match (args.file, project) {
+        (Some(p), _) => p,
+        (None, Some(name)) => load_projects()?.file(&name)?,
+        (None, None) => match env::var_os("MARKDONE_FILE") {
+            Some(p) if !p.is_empty() => PathBuf::from(p),
+            _ => match (&config.file, find_config()) {
+                (Some(file), Some(config_file)) => match config_file.parent() {
+                    Some(dir) => dir.join(file),
+                    None => file.clone(),
+                },
+                _ => PathBuf::from(DEFAULT_TASK_FILE),
+            },
+        },
+    };
+    if !args.allow_symlink {
+        check_task_path(&path)?;
+    }
+    if args.command.is_mutating() {
+        check_focus_lock(&path)?;
+    }
+    if (args.backup || config.backup) && args.command.is_mutating() && path.exists() {
+        backup_file(&path)?;
+    }
+    let history_limit = config.history.unwrap_or(DEFAULT_HISTORY_LIMIT);
+    let before = match args.command {
+        Commands::Undo
+        | Commands::Redo
+        | Commands::Bundle {
+            command: BundleCommand::Import { .. },
+        } => None,
+        ref command if command.is_mutating() && history_limit > 0 => fs::read_to_string(&path).ok(),
+        _ => None,
+    };
+    match args.command {
+        Commands::Add(options) => add_tasks(&path, options, &config, quiet, warnings)?,
+        Commands::Quick { text } => {
+            let task = parse_capture(&text.join(" "), &config.capture.grammar()?)?;
+            let options = AddOptions {
+                task: Some(task.task),
+                stdin: false,
+                from_file: None,
+                select: false,
+                complete: false,
+                url: None,
+                due: task.due,
+                target: None,
+                tags: task.tags,
+                priority: task.priority,
+                repeat: None,
+                force: false,
+                strict_tags: false,
+            };
+            add_tasks(&path, options, &config, quiet, warnings)?;
+        }
+        Commands::Check { ids, pattern } => {
+            let ids = match pattern {
+                Some(pattern) => vec![find_match(
+                    &path,
+                    &pattern,
+                    &[TaskStatus::Selected, TaskStatus::Incomplete],
+                )?],
+                None => flatten_ids(ids),
+            };
+            let author = config.author()?;
+            update_each(&path, &ids, quiet, |tasks, id| {
+                if config.journal {
+                    tasks.set_journal(true);
+                }
+                tasks.set_author(author.clone());
+                let mut message = format!("successfully checked task with id `{:?}`", id);
+                if let Some(next) = tasks.check(id)? {
+                    message.push_str(&format!("\nadded next occurrence with id `{:?}`", next));
+                }
+                Ok(message)
+            })?;
         }
+        Commands::Setup { force } => setup(force, quiet)?,
         Commands::Create(options) => {
             let path = match options.file {
                 Some(p) => p,
-                None => path,
+                None => path.clone(),
             };
             match path.exists() {
                 true => bail!("file `{:?}` already exists", &path),
                 false => {
-                    let mut file = File::create(&path)
+                    write_lines(&path, &TaskList::parse(&path, vec![]).to_lines())
                         .with_context(|| format!("could not create file `{:?}`", &path))?;
-                    file.write_all(
-                        b"### SELECTED\n\n---\n\n### INCOMPLETE\n\n---\n\n### COMPLETE\n\n---\n",
-                    )
-                    .with_context(|| format!("could not write to file `{:?}`", &path))?;
                     if !quiet {
                         eprintln!("successfully created `{:?}`", &path);
                     }
@@ -396,112 +4082,1244 @@ fn main() -> Result<()> {
             }
         }
         Commands::List(options) => {
-            let lines: Vec<String> = get_lines(&path)
-                .with_context(|| format!("could not read lines from file `{:?}`", path))?;
-            let mut sections: Vec<TaskStatus> = vec![];
-            let list_all =
-                options.all | !(options.complete | options.incomplete | options.selected);
-            if options.selected | list_all {
-                sections.push(TaskStatus::Selected);
+            let options = resolve_list_options(options, &mut config)?;
+            let sources = list_sources(&options, &path)?;
+            list_tasks(
+                &sources,
+                &options,
+                &config,
+                quiet,
+                hyperlinks,
+                color,
+                args.output,
+            )?;
+        }
+        Commands::Watch(options) => {
+            let list = resolve_list_options(options.list, &mut config)?;
+            let sources = list_sources(&list, &path)?;
+            let mut seen = vec![];
+            loop {
+                let modified: Vec<Option<SystemTime>> = sources
+                    .iter()
+                    .map(|(_, file)| fs::metadata(file).and_then(|m| m.modified()).ok())
+                    .collect();
+                if modified != seen {
+                    seen = modified;
+                    execute!(stdout(), Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+                    // An editor may leave the file half written, so keep watching.
+                    if let Err(error) = list_tasks(
+                        &sources,
+                        &list,
+                        &config,
+                        quiet,
+                        hyperlinks,
+                        color,
+                        args.output,
+                    ) {
+                        eprintln!("Error: {:?}", error);
+                    }
+                }
+                thread::sleep(Duration::from_millis(options.interval));
+            }
+        }
+        Commands::View { name, format } => {
+            let mut options = config
+                .views
+                .remove(&name)
+                .with_context(|| format!("could not find view `{}`", name))?;
+            options.legend |= config.list.legend;
+            if format.is_some() {
+                options.format = format;
+            }
+            list_tasks(
+                &list_sources(&options, &path)?,
+                &options,
+                &config,
+                quiet,
+                hyperlinks,
+                color,
+                args.output,
+            )?;
+        }
+        Commands::Archive { older_than } => {
+            let mut tasks = TaskList::load(&path)?;
+            let archive = archive_path(&path);
+            if !archive.exists() {
+                write_lines(&archive, &TaskList::parse(&archive, vec![]).to_lines())
+                    .with_context(|| format!("could not create file `{:?}`", archive))?;
+            }
+            let mut archived = TaskList::load(&archive)?;
+            if tasks.is_journal() {
+                archived.set_journal(true);
             }
-            if options.incomplete | list_all {
-                sections.push(TaskStatus::Incomplete);
+            let archivable = |t: &Task| {
+                t.task_status == TaskStatus::Complete
+                    && older_than.is_none_or(|cutoff| t.completed.is_some_and(|d| d <= cutoff))
+            };
+            let complete: Vec<Task> = tasks.iter().filter(|t| archivable(t)).cloned().collect();
+            tasks.tasks_mut().retain(|t| !archivable(t));
+            let count = complete.len();
+            archived.tasks_mut().splice(0..0, complete);
+            archived.save()?;
+            tasks.save()?;
+            if !quiet {
+                eprintln!("successfully archived {} tasks to `{:?}`", count, archive);
+            }
+        }
+        Commands::Project { command } => {
+            let mut projects = load_projects()?;
+            match command {
+                ProjectCommand::Add { name, file } => {
+                    if name.is_empty() || name.contains([':', '/']) {
+                        bail!("invalid project name `{}`", name);
+                    }
+                    if projects.projects.contains_key(&name) {
+                        bail!("project `{}` already exists", name);
+                    }
+                    let file = env::current_dir()?.join(file);
+                    let file = fs::canonicalize(&file).unwrap_or(file);
+                    projects.projects.insert(name.clone(), file.clone());
+                    save_projects(&projects)?;
+                    if !quiet {
+                        eprintln!("successfully added project `{}` for `{:?}`", name, file);
+                    }
+                }
+                ProjectCommand::List => {
+                    if !quiet {
+                        println!("project\tfile\n-------\t----");
+                    }
+                    for (name, file) in projects.projects.iter() {
+                        println!("{}\t{}", name, file.display());
+                    }
+                }
+                ProjectCommand::Remove { name } => {
+                    if projects.projects.remove(&name).is_none() {
+                        bail!("could not find project `{}`", name);
+                    }
+                    save_projects(&projects)?;
+                    if !quiet {
+                        eprintln!("successfully removed project `{}`", name);
+                    }
+                }
             }
-            if options.complete | list_all {
-                sections.push(TaskStatus::Complete);
+            return Ok(());
+        }
+        Commands::Search(options) => {
+            let pattern = if options.regex {
+                Regex::new(&options.query)
+                    .with_context(|| format!("invalid regular expression `{}`", options.query))?
+            } else {
+                text_pattern(&options.query)
+            };
+            let mut list = options.list;
+            list.pattern = Some(pattern);
+            list_tasks(
+                &list_sources(&list, &path)?,
+                &list,
+                &config,
+                quiet,
+                hyperlinks,
+                color,
+                args.output,
+            )?;
+        }
+        Commands::Select { ids } => {
+            update_each(&path, &flatten_ids(ids), quiet, |tasks, id| {
+                move_task_to_section(tasks, id, TaskStatus::Selected, vec![])?;
+                Ok(format!("successfully selected task with id `{:?}`", id))
+            })?;
+        }
+        Commands::Pop => {
+            let mut tasks = TaskList::load(&path)?;
+            if config.journal {
+                tasks.set_journal(true);
             }
-            let tasks = get_tasks_in_sections(lines, sections);
+            tasks.set_author(config.author()?);
+            let id = tasks
+                .next_selected()
+                .context("no selected task to check, select one with `markdone select`")?
+                .id;
+            let next = tasks.check(id)?;
+            tasks.save()?;
             if !quiet {
-                println!("status\t\tid\ttask\n------\t\t--\t----");
+                eprintln!("successfully checked task with id `{:?}`", id);
+                if let Some(next) = next {
+                    eprintln!("added next occurrence with id `{:?}`", next);
+                }
             }
-            let stdout = stdout();
-            let mut handle = stdout.lock();
-            for t in tasks.iter() {
-                writeln!(handle, "{}", t)?;
+        }
+        Commands::Move(options) => {
+            let mut tasks = TaskList::load(&path)?;
+            tasks.reorder(options.id, options.placement.placement())?;
+            tasks.save()?;
+            if !quiet {
+                eprintln!("successfully moved task with id `{:?}`", options.id);
             }
         }
-        Commands::Select { id } => {
-            move_task_to_section(id, path, TaskStatus::Selected, vec![])?;
+        Commands::Bump { id } => {
+            let mut tasks = TaskList::load(&path)?;
+            tasks.bump(id)?;
+            tasks.save()?;
             if !quiet {
-                eprintln!("successfully selected task with id `{:?}`", id);
+                eprintln!("successfully bumped task with id `{:?}`", id);
+            }
+        }
+        Commands::Next => {
+            let tasks = TaskList::load(&path)?;
+            let task = tasks
+                .next_selected()
+                .context("no selected task, select one with `markdone select`")?;
+            if args.output == OutputMode::ScreenReader {
+                println!("{}", task.describe(&dates));
+            } else {
+                println!(
+                    "{}\t{}",
+                    config.ids.format(task.id),
+                    task.render_text(hyperlinks, color, &dates)
+                );
             }
         }
         Commands::Uncheck(options) => {
-            let id = options.id;
             let new_section = if options.select {
                 TaskStatus::Selected
             } else {
                 TaskStatus::Incomplete
             };
-            move_task_to_section(
-                id,
-                path,
-                new_section,
-                vec![TaskStatus::Selected, TaskStatus::Incomplete],
-            )?;
+            update_each(&path, &flatten_ids(options.ids), quiet, |tasks, id| {
+                move_task_to_section(
+                    tasks,
+                    id,
+                    new_section,
+                    vec![TaskStatus::Selected, TaskStatus::Incomplete],
+                )?;
+                Ok(format!("successfully unchecked task with id `{:?}`", id))
+            })?;
+        }
+        Commands::Deselect { ids } => {
+            update_each(&path, &flatten_ids(ids), quiet, |tasks, id| {
+                move_task_to_section(
+                    tasks,
+                    id,
+                    TaskStatus::Incomplete,
+                    vec![TaskStatus::Incomplete, TaskStatus::Complete],
+                )?;
+                Ok(format!("successfully deselected task with id `{:?}`", id))
+            })?;
+        }
+        Commands::Edit { id, task, .. } => {
+            let mut tasks = TaskList::load(&path)?;
+            let t = tasks.get_mut(id)?;
+            t.task = match task {
+                Some(task) => task,
+                None => edit_in_editor(&t.task)?,
+            };
+            tasks.save()?;
             if !quiet {
-                eprintln!("successfully unchecked task with id `{:?}`", id);
+                eprintln!("successfully edited task with id `{:?}`", id);
             }
         }
-        Commands::Deselect { id } => {
-            move_task_to_section(
-                id,
-                path,
-                TaskStatus::Incomplete,
-                vec![TaskStatus::Incomplete, TaskStatus::Complete],
-            )?;
+        Commands::Note { id, text, .. } => {
+            let mut tasks = TaskList::load(&path)?;
+            let t = tasks.get_mut(id)?;
+            let text = match text {
+                Some(text) => text,
+                None => edit_file_in_editor(&t.notes.join("\n"))?,
+            };
+            t.notes = text
+                .trim_end()
+                .lines()
+                .map(|line| line.trim_end().to_string())
+                .filter(|line| !line.is_empty())
+                .collect();
+            tasks.save()?;
             if !quiet {
-                eprintln!("successfully deselected task with id `{:?}`", id);
+                eprintln!("successfully updated notes of task with id `{:?}`", id);
             }
         }
-        Commands::Edit { id, task } => {
-            let lines: Vec<String> = get_lines(&path)
-                .with_context(|| format!("could not read lines from file `{:?}`", path))?;
-            let mut tasks = get_tasks_in_sections(
-                lines,
-                vec![
-                    TaskStatus::Incomplete,
-                    TaskStatus::Complete,
-                    TaskStatus::Selected,
-                ],
-            );
-            match tasks.iter_mut().find(|e| e.id == id) {
-                Some(t) => {
-                    t.task = task;
+        Commands::Resolve { text, json } => {
+            let text = if text.is_empty() {
+                let mut input = String::new();
+                stdin()
+                    .read_to_string(&mut input)
+                    .context("could not read text from stdin")?;
+                input
+            } else {
+                text.join(" ")
+            };
+            let tasks = TaskList::load(&path)?;
+            let lines = get_lines(&path)?;
+            let mut values = vec![];
+            for id in find_references(&text, &config.ids) {
+                let Ok(task) = tasks.get(id) else {
+                    if warnings {
+                        eprintln!("warning: no task with id `{:?}` in `{:?}`", id, path);
+                    }
+                    continue;
+                };
+                let line = task_line(&lines, id).unwrap_or_default();
+                if json {
+                    let mut value = serde_json::to_value(task)?;
+                    if let Some(object) = value.as_object_mut() {
+                        object.insert(String::from("file"), path.display().to_string().into());
+                        object.insert(String::from("line"), line.into());
+                    }
+                    values.push(value);
+                } else {
+                    println!(
+                        "{}\t{}\t{}:{}\t{}",
+                        config.ids.format(id),
+                        task.task_status,
+                        path.display(),
+                        line,
+                        task.text()
+                    );
+                }
+            }
+            if json {
+                println!("{}", serde_json::to_string_pretty(&values)?);
+            }
+        }
+        Commands::Show { id } => {
+            let tasks = TaskList::load(&path)?;
+            for line in show_task(tasks.get(id)?, &config.ids, &dates) {
+                println!("{}", line);
+            }
+            let (sessions, minutes) = focus_time(&path, id);
+            if sessions > 0 {
+                println!("focused: {} minutes in {} sessions", minutes, sessions);
+            }
+        }
+        Commands::Delete {
+            ids,
+            pattern,
+            force,
+            yes,
+        } => {
+            let ids = match pattern {
+                Some(pattern) => vec![find_match(&path, &pattern, &TaskStatus::all())?],
+                None => flatten_ids(ids),
+            };
+            if ids.len() > 1 {
+                if !yes {
+                    confirm_delete(&path, &ids, force)?;
                 }
-                None => {
-                    bail!("could not find task with id `{:?}`", id);
+                // Undo history can be turned off, so keep a copy either way.
+                backup_file(&path)?;
+            }
+            update_each(&path, &ids, quiet, |tasks, id| {
+                if tasks.get(id)?.protected && !force {
+                    bail!(
+                        "task with id `{:?}` is protected, use `--force` to delete it",
+                        id
+                    );
+                }
+                tasks.remove(id)?;
+                Ok(format!("successfully deleted task with id `{:?}`", id))
+            })?;
+        }
+        Commands::Import(options) => {
+            let source = options.source;
+            let contents = fs::read_to_string(&source)
+                .with_context(|| format!("could not read file `{:?}`", source))?;
+            let delimiter = match source.extension() {
+                Some(ext) if ext == "tsv" => '\t',
+                _ => ',',
+            };
+            if options.map.is_some() && !matches!(options.format, ImportFormat::Csv) {
+                bail!("`--map` can only be used with `--format csv`");
+            }
+            let lists = match options.format {
+                ImportFormat::AppleReminders | ImportFormat::MicrosoftTodo => {
+                    options.list_map.unwrap_or_default()
+                }
+                _ if options.list_map.is_some() => bail!(
+                    "`--list-map` can only be used with `--format apple-reminders` or `--format microsoft-todo`"
+                ),
+                _ => ListMap::default(),
+            };
+            let imported: Vec<Task> = match options.format {
+                ImportFormat::Csv => import_csv(
+                    &contents,
+                    delimiter,
+                    &options.map.unwrap_or_default(),
+                    warnings,
+                ),
+                ImportFormat::GithubProjects => {
+                    import_github_projects(&contents, delimiter, warnings)
+                }
+                ImportFormat::AppleReminders => {
+                    import_apple_reminders(&contents, delimiter, &lists)
                 }
+                ImportFormat::MicrosoftTodo => import_microsoft_todo(&contents, &lists),
+                ImportFormat::Todotxt => import_todotxt(&contents),
+                ImportFormat::TaskwarriorJson => import_taskwarrior(&contents),
             }
-            write_tasks_to_file(path, tasks)?;
+            .with_context(|| format!("could not import tasks from `{:?}`", source))?;
+            let mut tasks = TaskList::load(&path)?;
+            tasks.set_author(config.author()?);
+            let count = imported.len();
+            let mut ids = vec![];
+            for imported in imported {
+                let task = tasks.add(imported.task, imported.task_status);
+                task.due = imported.due;
+                task.priority = imported.priority;
+                task.tags = imported.tags;
+                task.repeat = imported.repeat;
+                task.url = imported.url;
+                ids.push(task.id);
+            }
+            // Keep the imported tasks in file order, open ones at the top
+            // and selected ones at the end of the queue.
+            let added = ids
+                .iter()
+                .map(|&id| tasks.remove(id))
+                .collect::<Result<Vec<Task>>>()?;
+            let (selected, open): (Vec<Task>, Vec<Task>) = added
+                .into_iter()
+                .partition(|t| t.task_status == TaskStatus::Selected);
+            tasks.tasks_mut().splice(0..0, open);
+            tasks.tasks_mut().extend(selected);
+            tasks.save()?;
             if !quiet {
-                eprintln!("successfully edited task with id `{:?}`", id);
+                eprintln!("successfully imported {} tasks from `{:?}`", count, source);
             }
         }
-        Commands::Delete { id } => {
-            let lines: Vec<String> = get_lines(&path)
-                .with_context(|| format!("could not read lines from file `{:?}`", path))?;
-            let mut tasks = get_tasks_in_sections(
-                lines,
-                vec![
-                    TaskStatus::Incomplete,
-                    TaskStatus::Complete,
-                    TaskStatus::Selected,
-                ],
+        Commands::Export(options) => {
+            let tasks = TaskList::load(&path)?;
+            let exported = match options.format {
+                ExportFormat::Todotxt => {
+                    let mut lines: Vec<String> = tasks.iter().map(export_todotxt).collect();
+                    lines.push(String::new());
+                    lines.join("\n")
+                }
+                ExportFormat::Json => export_taskwarrior(&tasks)? + "\n",
+            };
+            match options.output {
+                Some(output) => {
+                    fs::write(&output, exported)
+                        .with_context(|| format!("could not write tasks to `{:?}`", output))?;
+                    if !quiet {
+                        eprintln!(
+                            "successfully exported {} tasks to `{:?}`",
+                            tasks.len(),
+                            output
+                        );
+                    }
+                }
+                None => print!("{}", exported),
+            }
+        }
+        Commands::Bundle { command } => match command {
+            BundleCommand::Export { file } => {
+                let count = export_bundle(&path, &file)?;
+                if !quiet {
+                    eprintln!("successfully bundled {} files into `{:?}`", count, file);
+                }
+            }
+            BundleCommand::Import { file, force } => {
+                let count = import_bundle(&path, &file, force, args.allow_symlink)?;
+                if !quiet {
+                    eprintln!("successfully restored {} files from `{:?}`", count, file);
+                }
+            }
+        },
+        Commands::Focus(options) => {
+            let tasks = TaskList::load(&path)?;
+            let task = match options.id {
+                Some(id) => tasks.get(id)?,
+                None => tasks
+                    .next_selected()
+                    .context("no selected task to focus on")?,
+            };
+            if task.task_status == TaskStatus::Complete {
+                bail!("cannot focus on complete task with id `{:?}`", task.id);
+            }
+            let length = Duration::from_secs(options.minutes * 60);
+            let started = Local::now();
+            let lock = state_dir(&path, FOCUS_DIR).join("lock");
+            if options.lock {
+                check_focus_lock(&path)?;
+                let dir = state_dir(&path, FOCUS_DIR);
+                fs::create_dir_all(&dir)
+                    .with_context(|| format!("could not create focus directory `{:?}`", dir))?;
+                let until = started + chrono::Duration::from_std(length)?;
+                fs::write(&lock, format!("{}\t{}\n", task.id, until.to_rfc3339()))
+                    .with_context(|| format!("could not write to file `{:?}`", lock))?;
+            }
+            let result = run_focus(task, length);
+            if options.lock {
+                fs::remove_file(&lock).ok();
+            }
+            let focused = result?;
+            if focused.as_secs() >= 60 {
+                log_focus_session(&path, task, started, focused)?;
+            }
+            if !quiet {
+                eprintln!(
+                    "finished {} minute focus session on task with id `{:?}`",
+                    focused.as_secs() / 60,
+                    task.id
+                );
+            }
+        }
+        Commands::Protect { id } => {
+            set_flag(&path, id, |t| &mut t.protected, true, "protected", quiet)?
+        }
+        Commands::Unprotect { id } => {
+            set_flag(&path, id, |t| &mut t.protected, false, "protected", quiet)?
+        }
+        Commands::Pin { id } => set_flag(&path, id, |t| &mut t.pinned, true, "pinned", quiet)?,
+        Commands::Unpin { id } => set_flag(&path, id, |t| &mut t.pinned, false, "pinned", quiet)?,
+        Commands::Star { id } => set_flag(&path, id, |t| &mut t.starred, true, "starred", quiet)?,
+        Commands::Unstar { id } => {
+            set_flag(&path, id, |t| &mut t.starred, false, "starred", quiet)?
+        }
+        Commands::Open { id } => {
+            let tasks = TaskList::load(&path)?;
+            let task = tasks.get(id)?;
+            let url = task
+                .url
+                .clone()
+                .or_else(|| find_url(&task.task))
+                .with_context(|| format!("task with id `{:?}` has no url", id))?;
+            open_url(&url)?;
+            if !quiet {
+                eprintln!("successfully opened `{}`", url);
+            }
+        }
+        Commands::Rollover { restore } => {
+            let mut tasks = TaskList::load(&path)?;
+            let (from, to) = match restore {
+                true => (TaskStatus::Incomplete, TaskStatus::Selected),
+                false => (TaskStatus::Selected, TaskStatus::Incomplete),
+            };
+            let ids: Vec<usize> = tasks
+                .iter()
+                .filter(|t| t.task_status == from && (t.carried || !restore))
+                .map(|t| t.id)
+                .collect();
+            for id in ids.iter() {
+                tasks.move_task(*id, to)?;
+                tasks.get_mut(*id)?.carried = !restore;
+            }
+            let count = ids.len();
+            tasks.save()?;
+            if !quiet {
+                if restore {
+                    eprintln!("successfully reselected {} carried over tasks", count);
+                } else {
+                    eprintln!("successfully carried over {} selected tasks", count);
+                }
+            }
+        }
+        Commands::Split { id, parts } => {
+            let mut list = TaskList::load(&path)?;
+            list.set_author(config.author()?);
+            let original = list.get(id)?.clone();
+            if original.task_status == TaskStatus::Complete {
+                bail!("cannot split complete task with id `{:?}`", id);
+            }
+            let mut ids: Vec<usize> = vec![];
+            for part in parts {
+                let child = list.add(part, original.task_status);
+                child.starred = original.starred;
+                child.url = original.url.clone();
+                child.target = original.target;
+                child.due = original.due;
+                child.tags = original.tags.clone();
+                child.priority = original.priority;
+                ids.push(child.id);
+            }
+            // Put the new tasks where the original was.
+            let children = ids
+                .iter()
+                .map(|&child| list.remove(child))
+                .collect::<Result<Vec<Task>>>()?;
+            let tasks = list.tasks_mut();
+            let index = tasks.iter().position(|t| t.id == id).unwrap();
+            tasks.splice(index..index, children);
+            let ids = ids
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            list.get_mut(id)?.task = format!("{} (split into {})", original.task, ids);
+            if config.journal {
+                list.set_journal(true);
+            }
+            let next = list.check(id)?;
+            list.save()?;
+            if !quiet {
+                eprintln!(
+                    "successfully split task with id `{:?}` into tasks {}",
+                    id, ids
+                );
+                if let Some(next) = next {
+                    eprintln!("added next occurrence with id `{:?}`", next);
+                }
+            }
+        }
+        Commands::MergeTasks { mut ids, force } => {
+            ids.sort();
+            ids.dedup();
+            if ids.len() < 2 {
+                bail!("need at least two different tasks to merge");
+            }
+            let mut list = TaskList::load(&path)?;
+            let (target, duplicates) = ids.split_first().unwrap();
+            list.merge(*target, duplicates, force)?;
+            list.save()?;
+            if !quiet {
+                eprintln!(
+                    "successfully merged {} tasks into task with id `{:?}`",
+                    duplicates.len(),
+                    target
+                );
+            }
+        }
+        Commands::Tag { id, name } => {
+            update_task(&path, id, |task| {
+                if task.tags.contains(&name) {
+                    bail!("task with id `{:?}` is already tagged `{}`", id, name);
+                }
+                task.tags.push(name.clone());
+                Ok(())
+            })?;
+            if !quiet {
+                eprintln!("successfully tagged task with id `{:?}` as `{}`", id, name);
+            }
+        }
+        Commands::Untag { id, name } => {
+            update_task(&path, id, |task| {
+                let index =
+                    task.tags.iter().position(|t| t == &name).with_context(|| {
+                        format!("task with id `{:?}` is not tagged `{}`", id, name)
+                    })?;
+                task.tags.remove(index);
+                Ok(())
+            })?;
+            if !quiet {
+                eprintln!(
+                    "successfully removed tag `{}` from task with id `{:?}`",
+                    name, id
+                );
+            }
+        }
+        Commands::Priority { id, level } => {
+            let priority = match level.as_str() {
+                "none" => None,
+                level => Some(parse_priority(level)?),
+            };
+            update_task(&path, id, |task| {
+                task.priority = priority;
+                Ok(())
+            })?;
+            if !quiet {
+                match priority {
+                    Some(p) => eprintln!(
+                        "successfully set priority of task with id `{:?}` to {}",
+                        id, p
+                    ),
+                    None => eprintln!("successfully cleared priority of task with id `{:?}`", id),
+                }
+            }
+        }
+        Commands::Status => {
+            let tasks = TaskList::load(&path)?;
+            for line in status_lines(&path, &tasks, &config.ids, &dates, hyperlinks, color) {
+                println!("{}", line);
+            }
+        }
+        Commands::Stats(options) => {
+            let mut tasks = TaskList::load(&path)?.into_tasks();
+            let archive = archive_path(&path);
+            let mut archived = 0;
+            if options.include_archive && archive.is_file() {
+                let archive = TaskList::load(&archive)?.into_tasks();
+                archived = archive.len();
+                tasks.extend(archive);
+            }
+            let since = match options.since {
+                Some(since) => since,
+                None => parse_since("7d")?,
+            };
+            print_stats(
+                &tasks,
+                archived,
+                since,
+                options.oldest,
+                args.output,
+                &dates,
+                &config.warnings,
             );
-            match tasks.iter_mut().position(|e| e.id == id) {
-                Some(i) => {
-                    tasks.remove(i);
+        }
+        Commands::Report { command } => match command {
+            ReportCommand::Aging { oldest } => {
+                let tasks = TaskList::load(&path)?;
+                let today = Local::now().date_naive();
+                let mut open: Vec<(i64, &Task)> = vec![];
+                let mut undated = 0;
+                for t in tasks
+                    .iter()
+                    .filter(|t| t.task_status == TaskStatus::Incomplete)
+                {
+                    match t.created {
+                        Some(created) => open.push(((today - created).num_days(), t)),
+                        None => undated += 1,
+                    }
+                }
+                let buckets = [
+                    ("0-7 days", 0..=7),
+                    ("8-30 days", 8..=30),
+                    ("31-90 days", 31..=90),
+                    ("older", 91..=i64::MAX),
+                ];
+                for (name, ages) in buckets {
+                    let count = open.iter().filter(|(age, _)| ages.contains(age)).count();
+                    println!("{}: {}", name, count);
+                }
+                if undated > 0 {
+                    println!("no added date: {}", undated);
+                }
+                open.sort_by_key(|(age, _)| std::cmp::Reverse(*age));
+                if oldest > 0 && !open.is_empty() {
+                    println!("oldest tasks:");
+                    for (age, t) in open.into_iter().take(oldest) {
+                        println!("  {}\t{} days\t{}", config.ids.format(t.id), age, t.task);
+                    }
+                }
+            }
+            ReportCommand::Rollup => {
+                let projects = load_projects()?.projects;
+                if projects.is_empty() {
+                    bail!("no projects registered, add one with `markdone project add`");
                 }
-                None => {
-                    bail!("could not find task with id `{:?}`", id);
+                let mut rows = vec![];
+                for (name, file) in projects {
+                    let tasks = TaskList::load(&file)
+                        .with_context(|| format!("could not load project `{}`", name))?;
+                    let count = |section: TaskStatus| {
+                        tasks.iter().filter(|t| t.task_status == section).count()
+                    };
+                    let changed = fs::metadata(&file).and_then(|m| m.modified()).ok();
+                    rows.push((
+                        changed,
+                        name,
+                        count(TaskStatus::Selected) + count(TaskStatus::Incomplete),
+                        count(TaskStatus::Selected),
+                        tasks.iter().filter(|t| t.is_overdue()).count(),
+                        modified_at(&file, &dates),
+                    ));
                 }
+                rows.sort_by_key(|row| row.0);
+                if !quiet {
+                    println!(
+                        "project\topen\tselected\toverdue\tlast change\n-------\t----\t--------\t-------\t-----------"
+                    );
+                }
+                for (_, name, open, selected, overdue, changed) in rows {
+                    println!(
+                        "{}\t{}\t{}\t{}\t{}",
+                        name,
+                        open,
+                        selected,
+                        overdue,
+                        changed.as_deref().unwrap_or("never")
+                    );
+                }
+            }
+        },
+        Commands::Heatmap => {
+            let mut tasks = TaskList::load(&path)?.into_tasks();
+            let archive = archive_path(&path);
+            if archive.is_file() {
+                tasks.extend(TaskList::load(&archive)?.into_tasks());
+            }
+            let since = dates.week_of(Local::now().date_naive() - chrono::Duration::weeks(52));
+            let mut completed: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+            for day in tasks.iter().filter_map(|t| t.completed) {
+                if day >= since {
+                    *completed.entry(day).or_default() += 1;
+                }
+            }
+            let total: usize = completed.values().sum();
+            if args.output == OutputMode::ScreenReader {
+                let mut per_month: BTreeMap<String, usize> = BTreeMap::new();
+                for (day, count) in completed.iter() {
+                    *per_month
+                        .entry(day.format("%Y-%m").to_string())
+                        .or_default() += count;
+                }
+                for (month, count) in per_month {
+                    println!("completed in {}: {}", month, count);
+                }
+            } else {
+                for line in heatmap_lines(&completed, since, &dates) {
+                    println!("{}", line);
+                }
+            }
+            match completed
+                .iter()
+                .max_by_key(|(day, count)| (**count, std::cmp::Reverse(**day)))
+            {
+                Some((day, count)) => println!(
+                    "{} tasks completed in the last year, most on {} ({})",
+                    total,
+                    dates.format(*day),
+                    count
+                ),
+                None => println!("no tasks completed in the last year"),
+            }
+        }
+        Commands::Conflicts => {
+            let lines: Vec<String> = get_lines(&path)
+                .with_context(|| format!("could not read lines from file `{:?}`", path))?;
+            let problems = find_conflicts(&lines);
+            for problem in problems.iter() {
+                println!("{}", problem);
+            }
+            if !problems.is_empty() {
+                bail!("found {} problems in `{:?}`", problems.len(), path);
+            }
+            if !quiet {
+                eprintln!("no problems found in `{:?}`", path);
+            }
+        }
+        Commands::Bench(options) => {
+            if !quiet {
+                eprintln!(
+                    "timing {} runs with {} generated tasks",
+                    options.runs, options.tasks
+                );
+            }
+            for (step, time) in run_bench(options.tasks, options.runs)? {
+                println!("{}\t{:.3}ms", step, time.as_secs_f64() * 1000.0);
+            }
+        }
+        Commands::Badge { output } => {
+            let tasks = TaskList::load(&path)?;
+            let done = tasks
+                .iter()
+                .filter(|t| t.task_status == TaskStatus::Complete)
+                .count();
+            let svg = badge_svg(tasks.len() - done, done);
+            match output {
+                Some(output) => {
+                    fs::write(&output, svg)
+                        .with_context(|| format!("could not write badge to `{:?}`", output))?;
+                    if !quiet {
+                        eprintln!("successfully wrote badge to `{:?}`", output);
+                    }
+                }
+                None => print!("{}", svg),
+            }
+        }
+        Commands::Lint => {
+            let lines: Vec<String> = get_lines(&path)
+                .with_context(|| format!("could not read lines from file `{:?}`", path))?;
+            if let Some(rule) = config
+                .lint
+                .disable
+                .iter()
+                .find(|r| !LINT_RULES.contains(&r.as_str()))
+            {
+                bail!(
+                    "unknown lint rule `{}`, expected one of {}",
+                    rule,
+                    LINT_RULES.join(", ")
+                );
+            }
+            let problems = lint(&lines, &config.lint);
+            for (line, rule, message) in problems.iter() {
+                println!("{}:{}: {}: {}", path.display(), line, rule, message);
+            }
+            if !problems.is_empty() {
+                bail!("found {} lint problems in `{:?}`", problems.len(), path);
+            }
+            if !quiet {
+                eprintln!("no lint problems found in `{:?}`", path);
+            }
+        }
+        Commands::External(args) => {
+            let (name, rest) = args.split_first().context("missing command name")?;
+            let program = format!("markdone-{}", name);
+            let mut command = process::Command::new(&program);
+            command
+                .args(rest)
+                .env(
+                    "MARKDONE_FILE",
+                    fs::canonicalize(&path).unwrap_or_else(|_| path.clone()),
+                )
+                .env("MARKDONE_QUIET", if quiet { "1" } else { "0" })
+                .env("MARKDONE_WARNINGS", if warnings { "1" } else { "0" })
+                .env("MARKDONE_COLOR", if color { "1" } else { "0" })
+                .env("MARKDONE_HYPERLINKS", if hyperlinks { "1" } else { "0" })
+                .env("MARKDONE_READ_ONLY", if read_only { "1" } else { "0" });
+            let status = match command.status() {
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => bail!(
+                    "unknown command `{}`, and no `{}` found on PATH",
+                    name,
+                    program
+                ),
+                result => result.with_context(|| format!("could not run `{}`", program))?,
+            };
+            if !status.success() {
+                process::exit(status.code().unwrap_or(1));
+            }
+        }
+        Commands::Tui => tui::run(&path, config.journal, config.author()?)?,
+        Commands::ShellInit { shell } => {
+            let script = match shell {
+                Shell::Bash => include_str!("shell/init.bash"),
+                Shell::Zsh => include_str!("shell/init.zsh"),
+                Shell::Fish => include_str!("shell/init.fish"),
+            };
+            print!("{}", script);
+            return Ok(());
+        }
+        Commands::Schema => {
+            println!("{}", serde_json::to_string_pretty(&list_schema())?);
+            return Ok(());
+        }
+        Commands::Completions { shell } => {
+            let mut command = Cli::command();
+            clap_complete::generate(shell, &mut command, "markdone", &mut stdout());
+            let dynamic = match shell {
+                clap_complete::Shell::Bash => include_str!("shell/complete.bash"),
+                clap_complete::Shell::Zsh => include_str!("shell/complete.zsh"),
+                clap_complete::Shell::Fish => include_str!("shell/complete.fish"),
+                _ => "",
+            };
+            print!("{}", dynamic);
+            return Ok(());
+        }
+        Commands::CompleteIds { status } => {
+            // completion must stay quiet when there is no task file here
+            let Ok(tasks) = TaskList::load(&path) else {
+                return Ok(());
+            };
+            for task in tasks.iter().filter(|t| {
+                (t.task_status == TaskStatus::Complete) == (status == CompletionStatus::Complete)
+            }) {
+                println!("{}\t{}", config.ids.format(task.id), task.task);
+            }
+            return Ok(());
+        }
+        Commands::Undo => {
+            let dir = history_dir(&path);
+            if !step_history(&path, &dir, &dir.join("redo"))? {
+                bail!("nothing to undo in `{:?}`", path);
+            }
+            if !quiet {
+                eprintln!("successfully undid last change to `{:?}`", path);
+            }
+        }
+        Commands::Redo => {
+            let dir = history_dir(&path);
+            if !step_history(&path, &dir.join("redo"), &dir)? {
+                bail!("nothing to redo in `{:?}`", path);
             }
-            write_tasks_to_file(path, tasks)?;
             if !quiet {
-                eprintln!("successfully deleted task with id `{:?}`", id);
+                eprintln!("successfully redid last undone change to `{:?}`", path);
             }
         }
     };
-    return Ok(());
+    let warn_limits = warnings && !config.warnings.is_empty();
+    if before.is_none() && !warn_limits {
+        return Ok(());
+    }
+    // Read the task file once for both the history and the limits.
+    let after = match fs::read_to_string(&path) {
+        Ok(after) => after,
+        Err(_) => return Ok(()),
+    };
+    if let Some(before) = before {
+        record_history(&path, &before, &after, history_limit)?;
+    }
+    if warn_limits {
+        let lines = after.lines().map(String::from).collect();
+        warn_section_limits(
+            &TaskList::parse(&path, lines).into_tasks(),
+            &config.warnings,
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_view_with_dates() {
+        let config: Config = toml::from_str(
+            "[views.soon]\nincomplete = true\ndue_before = \"+3d\"\ncompleted_since = \"7d\"\ninclude_archive = true\nall_projects = true",
+        )
+        .unwrap();
+        let view = &config.views["soon"];
+        let today = Local::now().date_naive();
+        assert_eq!(view.due_before, Some(today + chrono::Duration::days(3)));
+        assert_eq!(
+            view.completed_since,
+            Some(today - chrono::Duration::days(7))
+        );
+        assert!(view.incomplete && view.include_archive && view.all_projects);
+        assert!(toml::from_str::<Config>("[views.bad]\ndue_before = \"soon\"").is_err());
+    }
+
+    #[test]
+    fn reads_back_prefixed_ids() {
+        let config: Config = toml::from_str("[ids]\nprefix = \"T-\"\npadding = 3").unwrap();
+        let id = config.ids.format(12);
+        assert_eq!(id, "T-012");
+        assert_eq!(parse_id(&id).unwrap(), 12);
+        assert_eq!(parse_id_range("T-010-T-012").unwrap(), 10..=12);
+        assert!(toml::from_str::<Config>("[ids]\nprefix = \"T\"").is_err());
+    }
+
+    #[test]
+    fn parses_quoted_csv_fields() {
+        let contents = "Title,Notes\n\"a, b\",\"line one\nline \"\"two\"\"\"\r\nc,d\n\n";
+        let rows = parse_delimited(contents, ',').unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec!["Title", "Notes"],
+                vec!["a, b", "line one\nline \"two\""],
+                vec!["c", "d"],
+            ]
+        );
+        assert!(parse_delimited("Title\n\"open", ',').is_err());
+    }
+
+    #[test]
+    fn imports_csv_with_column_map() {
+        let contents = "Name,State,Due,Prio\nfirst,done,2024-07-01,urgent\nsecond,doing,,normal\nthird,,,someday\n";
+        let map = parse_column_map("title=Name,status=State,due=Due,priority=Prio").unwrap();
+        let tasks = import_csv(contents, ',', &map, false).unwrap();
+        let fields: Vec<_> = tasks
+            .iter()
+            .map(|t| (t.task.as_str(), t.task_status, t.priority))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("first", TaskStatus::Complete, Some(Priority::High)),
+                ("second", TaskStatus::Selected, Some(Priority::Medium)),
+                ("third", TaskStatus::Incomplete, None),
+            ]
+        );
+        assert_eq!(tasks[0].due, NaiveDate::from_ymd_opt(2024, 7, 1));
+        let tasks = import_csv("Title\nonly\n", ',', &ColumnMap::default(), false).unwrap();
+        assert_eq!(tasks[0].task_status, TaskStatus::Incomplete);
+        assert!(import_csv(
+            contents,
+            ',',
+            &parse_column_map("title=Task").unwrap(),
+            false
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn imports_github_projects() {
+        let contents = "Title,Status,Assignees,URL\nfix,Done,\"octocat, 12345\",https://github.com/o/r/issues/1\nplan,In Progress,,\n";
+        let tasks = import_github_projects(contents, ',', false).unwrap();
+        assert_eq!(tasks[0].task_status, TaskStatus::Complete);
+        assert_eq!(tasks[0].tags, vec!["octocat"]);
+        assert_eq!(
+            tasks[0].url.as_deref(),
+            Some("https://github.com/o/r/issues/1")
+        );
+        assert_eq!(tasks[1].task_status, TaskStatus::Selected);
+        assert_eq!(tasks[1].url, None);
+    }
+
+    #[test]
+    fn imports_apple_reminders() {
+        let contents = "Title,List,Completed,Due Date,Priority\na,Today,No,2024-07-01 09:00,1\nb,Today,Yes,,9\nc,Work,No,,medium\n";
+        let lists = parse_list_map("Today=selected").unwrap();
+        let tasks = import_apple_reminders(contents, ',', &lists).unwrap();
+        let fields: Vec<_> = tasks.iter().map(|t| (t.task_status, t.priority)).collect();
+        assert_eq!(
+            fields,
+            vec![
+                (TaskStatus::Selected, Some(Priority::High)),
+                (TaskStatus::Complete, Some(Priority::Low)),
+                (TaskStatus::Incomplete, Some(Priority::Medium)),
+            ]
+        );
+        assert_eq!(tasks[0].due, NaiveDate::from_ymd_opt(2024, 7, 1));
+    }
+
+    #[test]
+    fn imports_microsoft_todo() {
+        let contents = r#"{"value": [{"displayName": "Today", "tasks": [
+            {"title": "a", "status": "notStarted", "importance": "high",
+             "dueDateTime": {"dateTime": "2024-07-01T00:00:00.0000000"}},
+            {"title": "b", "status": "completed", "importance": "low"},
+            {"title": " ", "status": "notStarted"}
+        ]}]}"#;
+        let lists = parse_list_map("Today=selected").unwrap();
+        let tasks = import_microsoft_todo(contents, &lists).unwrap();
+        let fields: Vec<_> = tasks.iter().map(|t| (t.task_status, t.priority)).collect();
+        assert_eq!(
+            fields,
+            vec![
+                (TaskStatus::Selected, Some(Priority::High)),
+                (TaskStatus::Complete, Some(Priority::Low)),
+            ]
+        );
+        assert_eq!(tasks[0].due, NaiveDate::from_ymd_opt(2024, 7, 1));
+    }
+
+    #[test]
+    fn round_trips_todotxt_and_taskwarrior() {
+        let file = "### SELECTED\n\n- [ ] **0**: write report #work ⏫ 📅 2024-07-01\n\n---\n\n### INCOMPLETE\n\n- [ ] **1**: water plants 🔽 🔁 weekly\n\n---\n\n### COMPLETE\n\n- [x] **2**: pay rent #home 🔼 ✅ 2024-06-01\n\n---";
+        let list = TaskList::parse(
+            Path::new("markdone.md"),
+            file.lines().map(String::from).collect(),
+        );
+        let fields = |tasks: &[Task]| -> Vec<_> {
+            tasks
+                .iter()
+                .map(|t| {
+                    (
+                        t.task.clone(),
+                        t.task_status,
+                        t.priority,
+                        t.due,
+                        t.tags.clone(),
+                    )
+                })
+                .collect()
+        };
+        let todotxt: Vec<String> = list.iter().map(export_todotxt).collect();
+        let imported = import_todotxt(&todotxt.join("\n")).unwrap();
+        // todo.txt has no selected tasks, they come back open.
+        let mut expected = fields(&list.iter().cloned().collect::<Vec<_>>());
+        expected[0].1 = TaskStatus::Incomplete;
+        assert_eq!(fields(&imported), expected);
+        assert_eq!(imported[1].repeat, list.get(1).unwrap().repeat);
+        let imported = import_taskwarrior(&export_taskwarrior(&list).unwrap()).unwrap();
+        assert_eq!(
+            fields(&imported),
+            fields(&list.iter().cloned().collect::<Vec<_>>())
+        );
+    }
+
+    /// Check `value` against the parts of JSON Schema that `list_schema`
+    /// uses.
+    fn matches_schema(value: &serde_json::Value, schema: &serde_json::Value) -> bool {
+        use serde_json::Value;
+        if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+            return options.contains(value);
+        }
+        let type_matches = |name: &str| match name {
+            "null" => value.is_null(),
+            "boolean" => value.is_boolean(),
+            "integer" => value.is_u64() || value.is_i64(),
+            "string" => value.is_string(),
+            "array" => value.is_array(),
+            "object" => value.is_object(),
+            _ => false,
+        };
+        let types_match = match &schema["type"] {
+            Value::String(name) => type_matches(name),
+            Value::Array(names) => names.iter().filter_map(Value::as_str).any(type_matches),
+            _ => true,
+        };
+        if !types_match {
+            return false;
+        }
+        if let (Some(items), Some(values)) = (schema.get("items"), value.as_array()) {
+            return values.iter().all(|v| matches_schema(v, items));
+        }
+        if let Some(object) = value.as_object() {
+            let properties = &schema["properties"];
+            let required = schema["required"].as_array().cloned().unwrap_or_default();
+            return required
+                .iter()
+                .filter_map(Value::as_str)
+                .all(|key| object.contains_key(key))
+                && object.iter().all(|(key, v)| match properties.get(key) {
+                    Some(property) => matches_schema(v, property),
+                    None => schema["additionalProperties"] != false,
+                });
+        }
+        true
+    }
+
+    #[test]
+    fn list_json_matches_schema() {
+        let file = "### INCOMPLETE\n\n- [ ] **4**: plan trip #travel ⏫ 📅 2024-07-01 🔁 weekly 🔗 https://example.com ⭐ ➕ 2024-06-01 👤 alice ✏ 2024-06-02\n  book hotel\n\n---";
+        let list = TaskList::parse(
+            Path::new("markdone.md"),
+            file.lines().map(String::from).collect(),
+        );
+        let row = task_json(list.get(4).unwrap(), Some("home"), Some("markdone.md")).unwrap();
+        let schema = list_schema();
+        assert!(matches_schema(
+            &serde_json::Value::Array(vec![row.clone()]),
+            &schema
+        ));
+        let mut extra = row.clone();
+        extra["estimate"] = 3.into();
+        assert!(!matches_schema(&extra, &schema["items"]));
+        let mut missing = row;
+        missing.as_object_mut().unwrap().remove("status");
+        assert!(!matches_schema(&missing, &schema["items"]));
+    }
+
+    #[test]
+    fn parses_view_expressions() {
+        let today = Local::now().date_naive();
+        let file = format!(
+            "### SELECTED\n\n- [ ] **0**: a ⏫\n\n---\n\n### INCOMPLETE\n\n- [ ] **1**: b #home 📅 {}\n- [ ] **2**: c 📅 2099-01-01\n- [ ] **3**: d 🔽\n\n---\n\n### COMPLETE\n\n- [x] **4**: e ⏫\n\n---",
+            today.format(DATE_FORMAT)
+        );
+        let list = TaskList::parse(
+            Path::new("markdone.md"),
+            file.lines().map(String::from).collect(),
+        );
+        let matching = |text: &str| -> Vec<usize> {
+            let expr = parse_expression(text).unwrap();
+            list.iter()
+                .filter(|t| expr.matches(t))
+                .map(|t| t.id)
+                .collect()
+        };
+        assert_eq!(
+            matching("status!=complete and (priority=high or due<+2d)"),
+            vec![0, 1]
+        );
+        assert_eq!(matching("priority = low or tag=home"), vec![1, 3]);
+        assert_eq!(
+            matching("not (status=incomplete) and priority=high"),
+            vec![0, 4]
+        );
+        assert_eq!(
+            matching("status=incomplete AND due<=2099-01-01"),
+            vec![1, 2]
+        );
+        assert!(parse_expression("status=complete and").is_err());
+        assert!(parse_expression("(status=complete").is_err());
+        assert!(parse_expression("status<complete").is_err());
+        assert!(parse_expression("size=big").is_err());
+
+        let config: Config = toml::from_str(
+            "[views]\nurgent = \"status!=complete and priority=high\"\n[views.home]\ntags = [\"home\"]",
+        )
+        .unwrap();
+        let filter = config.views["urgent"].filter();
+        assert_eq!(
+            list.iter()
+                .filter(|t| filter.matches(t))
+                .map(|t| t.id)
+                .collect::<Vec<_>>(),
+            vec![0]
+        );
+        assert_eq!(config.views["home"].tags, vec!["home"]);
+        assert!(toml::from_str::<Config>("[views]\nbad = \"status=\"").is_err());
+    }
 }
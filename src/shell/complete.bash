@@ -0,0 +1,34 @@
+
+# complete task ids from the current task file
+__markdone_complete() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" command="" skip="" position=0 word i
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        word="${COMP_WORDS[i]}"
+        if [ -n "$skip" ]; then
+            skip=""
+        elif [ -n "$command" ]; then
+            [[ "$word" != -* ]] && position=$((position + 1))
+        else
+            case "$word" in
+                -f|--file|--project|--output|--color) skip=1 ;;
+                -*) ;;
+                *) command="$word" ;;
+            esac
+        fi
+    done
+    local status=open
+    case "$command" in
+        check|select|deselect|delete|merge-tasks) ;;
+        uncheck) status=complete ;;
+        show|edit|note|bump|move|protect|unprotect|pin|unpin|star|unstar|open|tag|untag|priority|split|focus)
+            [ "$position" -eq 0 ] || command="" ;;
+        *) command="" ;;
+    esac
+    if [ -n "$command" ] && [[ "$cur" != -* ]]; then
+        COMPREPLY=($(compgen -W "$(markdone complete-ids --status "$status" 2>/dev/null | cut -f1)" -- "$cur"))
+        return
+    fi
+    _markdone "$@"
+}
+
+complete -F __markdone_complete -o bashdefault -o default markdone
@@ -0,0 +1,5 @@
+
+# complete task ids from the current task file
+set -l __markdone_id_commands check select deselect delete merge-tasks show edit note bump move protect unprotect pin unpin star unstar open tag untag priority split focus
+complete -c markdone -n "__fish_seen_subcommand_from $__markdone_id_commands" -f -a "(markdone complete-ids --status open 2>/dev/null)"
+complete -c markdone -n "__fish_seen_subcommand_from uncheck" -f -a "(markdone complete-ids --status complete 2>/dev/null)"
@@ -0,0 +1,33 @@
+# markdone shell integration for bash
+# add to ~/.bashrc: eval "$(markdone shell-init bash)"
+
+__markdone_find_file() {
+    if [ -n "$MARKDONE_FILE" ] && [ -z "$__markdone_auto_file" ]; then
+        return
+    fi
+    local dir="$PWD"
+    while :; do
+        if [ -f "$dir/markdone.md" ]; then
+            export MARKDONE_FILE="$dir/markdone.md"
+            __markdone_auto_file=1
+            return
+        fi
+        [ "$dir" = "/" ] && break
+        dir="$(dirname "$dir")"
+    done
+    unset MARKDONE_FILE __markdone_auto_file
+}
+
+__markdone_pick() {
+    command -v fzf >/dev/null || return
+    local id
+    id="$(markdone -q list --not-status complete | fzf | cut -f2)"
+    READLINE_LINE="${READLINE_LINE:0:$READLINE_POINT}$id${READLINE_LINE:$READLINE_POINT}"
+    READLINE_POINT=$((READLINE_POINT + ${#id}))
+}
+
+case ";$PROMPT_COMMAND;" in
+    *";__markdone_find_file;"*) ;;
+    *) PROMPT_COMMAND="__markdone_find_file${PROMPT_COMMAND:+;$PROMPT_COMMAND}" ;;
+esac
+bind -x '"\C-t": __markdone_pick'
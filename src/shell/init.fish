@@ -0,0 +1,30 @@
+# markdone shell integration for fish
+# add to ~/.config/fish/config.fish: markdone shell-init fish | source
+
+function __markdone_find_file --on-variable PWD
+    if set -q MARKDONE_FILE; and not set -q __markdone_auto_file
+        return
+    end
+    set -l dir $PWD
+    while true
+        if test -f "$dir/markdone.md"
+            set -gx MARKDONE_FILE "$dir/markdone.md"
+            set -g __markdone_auto_file 1
+            return
+        end
+        test "$dir" = /; and break
+        set dir (dirname $dir)
+    end
+    set -e MARKDONE_FILE
+    set -e __markdone_auto_file
+end
+
+function __markdone_pick
+    type -q fzf; or return
+    set -l id (markdone -q list --not-status complete | fzf | cut -f2)
+    commandline -i -- $id
+    commandline -f repaint
+end
+
+__markdone_find_file
+bind \ct __markdone_pick
@@ -0,0 +1,298 @@
+use anyhow::Result;
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use markdone::{Task, TaskList, TaskStatus};
+use std::{
+    io::{stdout, Stdout, Write},
+    path::PathBuf,
+};
+
+const HELP: &str = "j/k move  x check  s select  a add  e edit  d delete  w save  q quit";
+
+enum Mode {
+    Normal,
+    Add(String),
+    Edit(String),
+    ConfirmDelete,
+    ConfirmQuit,
+}
+
+struct App {
+    list: TaskList,
+    cursor: usize,
+    offset: usize,
+    modified: bool,
+    mode: Mode,
+    message: String,
+}
+
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn new() -> Result<TerminalGuard> {
+        terminal::enable_raw_mode()?;
+        execute!(stdout(), EnterAlternateScreen, cursor::Hide)?;
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        execute!(stdout(), cursor::Show, LeaveAlternateScreen).ok();
+        terminal::disable_raw_mode().ok();
+    }
+}
+
+pub fn run(path: &PathBuf, journal: bool, author: Option<String>) -> Result<()> {
+    let mut list = TaskList::load(path)?;
+    if journal {
+        list.set_journal(true);
+    }
+    list.set_author(author);
+    let mut app = App {
+        list,
+        cursor: 0,
+        offset: 0,
+        modified: false,
+        mode: Mode::Normal,
+        message: String::new(),
+    };
+    app.sort();
+    let _guard = TerminalGuard::new()?;
+    let mut out = stdout();
+    loop {
+        app.draw(&mut out)?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Release && !app.handle_key(key)? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl App {
+    fn tasks(&mut self) -> &mut Vec<Task> {
+        self.list.tasks_mut()
+    }
+
+    fn sort(&mut self) {
+        let rank = |status: &TaskStatus| {
+            TaskStatus::all()
+                .iter()
+                .position(|s| s == status)
+                .unwrap_or(0)
+        };
+        self.tasks().sort_by_key(|t| rank(&t.task_status));
+    }
+
+    fn current(&mut self) -> Option<&mut Task> {
+        let cursor = self.cursor;
+        self.tasks().get_mut(cursor)
+    }
+
+    fn set_status(&mut self, status: TaskStatus) {
+        let id = match self.current() {
+            Some(task) => task.id,
+            None => return,
+        };
+        if status == TaskStatus::Complete {
+            self.list.check(id).ok();
+        } else {
+            self.list.move_task(id, status).ok();
+        }
+        self.modified = true;
+        self.sort();
+        self.cursor = self.tasks().iter().position(|t| t.id == id).unwrap_or(0);
+    }
+
+    fn save(&mut self) -> Result<()> {
+        self.list.save()?;
+        self.modified = false;
+        self.message = format!("saved `{}`", self.list.path().display());
+        Ok(())
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Result<bool> {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Ok(false);
+        }
+        self.message.clear();
+        match &mut self.mode {
+            Mode::Add(buffer) | Mode::Edit(buffer) => match key.code {
+                KeyCode::Esc => self.mode = Mode::Normal,
+                KeyCode::Backspace => {
+                    buffer.pop();
+                }
+                KeyCode::Char(c) => buffer.push(c),
+                KeyCode::Enter => {
+                    let text = buffer.trim().to_string();
+                    let mode = std::mem::replace(&mut self.mode, Mode::Normal);
+                    if !text.is_empty() {
+                        match mode {
+                            Mode::Add(_) => self.add(text),
+                            _ => {
+                                if let Some(task) = self.current() {
+                                    task.task = text;
+                                }
+                            }
+                        }
+                        self.modified = true;
+                    }
+                }
+                _ => {}
+            },
+            Mode::ConfirmDelete => {
+                if key.code == KeyCode::Char('y') && self.cursor < self.tasks().len() {
+                    let cursor = self.cursor;
+                    self.tasks().remove(cursor);
+                    self.cursor = self.cursor.min(self.tasks().len().saturating_sub(1));
+                    self.modified = true;
+                }
+                self.mode = Mode::Normal;
+            }
+            Mode::ConfirmQuit => {
+                if key.code == KeyCode::Char('y') {
+                    return Ok(false);
+                }
+                self.mode = Mode::Normal;
+            }
+            Mode::Normal => match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => {
+                    if !self.modified {
+                        return Ok(false);
+                    }
+                    self.mode = Mode::ConfirmQuit;
+                }
+                KeyCode::Char('j') | KeyCode::Down if self.cursor + 1 < self.tasks().len() => {
+                    self.cursor += 1;
+                }
+                KeyCode::Char('k') | KeyCode::Up => {
+                    self.cursor = self.cursor.saturating_sub(1);
+                }
+                KeyCode::Char('x') | KeyCode::Char(' ') => {
+                    let status = match self.current().map(|t| t.task_status) {
+                        Some(TaskStatus::Complete) => TaskStatus::Incomplete,
+                        _ => TaskStatus::Complete,
+                    };
+                    self.set_status(status);
+                }
+                KeyCode::Char('s') => {
+                    let status = match self.current().map(|t| t.task_status) {
+                        Some(TaskStatus::Selected) => TaskStatus::Incomplete,
+                        _ => TaskStatus::Selected,
+                    };
+                    self.set_status(status);
+                }
+                KeyCode::Char('a') => self.mode = Mode::Add(String::new()),
+                KeyCode::Char('e') => {
+                    if let Some(task) = self.current() {
+                        let text = task.task.clone();
+                        self.mode = Mode::Edit(text);
+                    }
+                }
+                KeyCode::Char('d') => match self.current() {
+                    Some(task) if task.protected => {
+                        self.message = format!("task with id `{}` is protected", task.id);
+                    }
+                    Some(_) => self.mode = Mode::ConfirmDelete,
+                    None => {}
+                },
+                KeyCode::Char('w') => self.save()?,
+                _ => {}
+            },
+        }
+        Ok(true)
+    }
+
+    fn add(&mut self, text: String) {
+        self.list.add(text, TaskStatus::Incomplete);
+        self.sort();
+        self.cursor = self
+            .tasks()
+            .iter()
+            .position(|t| t.task_status != TaskStatus::Selected)
+            .unwrap_or(0);
+    }
+
+    fn draw(&mut self, out: &mut Stdout) -> Result<()> {
+        let (width, height) = terminal::size()?;
+        let (width, height) = (width as usize, height as usize);
+        let mut rows: Vec<(Option<usize>, String)> = vec![];
+        for section in TaskStatus::all() {
+            rows.push((None, section.to_string().to_uppercase()));
+            for (i, task) in self.tasks().iter().enumerate() {
+                if task.task_status == section {
+                    let check = if section == TaskStatus::Complete {
+                        'x'
+                    } else {
+                        ' '
+                    };
+                    rows.push((
+                        Some(i),
+                        format!("  [{}] {}: {}", check, task.id, task.text()),
+                    ));
+                }
+            }
+            rows.push((None, String::new()));
+        }
+        let visible = height.saturating_sub(3).max(1);
+        let cursor_row = rows
+            .iter()
+            .position(|(i, _)| *i == Some(self.cursor))
+            .unwrap_or(0);
+        if cursor_row < self.offset {
+            self.offset = cursor_row;
+        } else if cursor_row >= self.offset + visible {
+            self.offset = cursor_row + 1 - visible;
+        }
+        queue!(out, Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+        let title = format!(
+            "markdone: {}{}",
+            self.list.path().display(),
+            if self.modified { " [modified]" } else { "" }
+        );
+        queue!(
+            out,
+            SetAttribute(Attribute::Bold),
+            Print(truncate(&title, width)),
+            SetAttribute(Attribute::Reset)
+        )?;
+        for (line, (index, text)) in rows.iter().skip(self.offset).take(visible).enumerate() {
+            queue!(out, cursor::MoveTo(0, line as u16 + 1))?;
+            if index.is_some() && *index == Some(self.cursor) {
+                queue!(
+                    out,
+                    SetAttribute(Attribute::Reverse),
+                    Print(truncate(text, width)),
+                    SetAttribute(Attribute::Reset)
+                )?;
+            } else {
+                queue!(out, Print(truncate(text, width)))?;
+            }
+        }
+        let status = match &self.mode {
+            Mode::Normal if self.message.is_empty() => HELP.to_string(),
+            Mode::Normal => self.message.clone(),
+            Mode::Add(buffer) => format!("add: {}_", buffer),
+            Mode::Edit(buffer) => format!("edit: {}_", buffer),
+            Mode::ConfirmDelete => String::from("delete task? (y/n)"),
+            Mode::ConfirmQuit => String::from("quit without saving? (y/n)"),
+        };
+        queue!(
+            out,
+            cursor::MoveTo(0, height.saturating_sub(1) as u16),
+            Print(truncate(&status, width))
+        )?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}